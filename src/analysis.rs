@@ -0,0 +1,228 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt::{Debug, Display},
+  hash::Hash,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{Encloser, Operator, SyntaxContext, SyntaxGraph};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AmbiguityKind {
+  PrefixMarker {
+    shorter: String,
+    longer: String,
+    shadowed: bool,
+  },
+  CloserIsOpener {
+    encloser: String,
+    opener: String,
+  },
+  SwallowedCloser {
+    encloser: String,
+  },
+  EscapeCollision {
+    marker: String,
+  },
+  OperatorStartsLeaf {
+    operator: String,
+  },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ambiguity<C: Clone + Debug + PartialEq + Eq + Hash> {
+  pub context: C,
+  pub kind: AmbiguityKind,
+  pub example: String,
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash> Display for Ambiguity<C> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use AmbiguityKind::*;
+    match &self.kind {
+      PrefixMarker {
+        shorter,
+        longer,
+        shadowed: true,
+      } => {
+        write!(f, "\"{shorter}\" is checked first and shadows \"{longer}\"")
+      }
+      PrefixMarker {
+        shorter, longer, ..
+      } => write!(f, "\"{shorter}\" is a prefix of \"{longer}\""),
+      CloserIsOpener { encloser, opener } => write!(
+        f,
+        "the closer of \"{encloser}\" shadows the marker of \"{opener}\""
+      ),
+      SwallowedCloser { encloser } => {
+        write!(f, "the closer of \"{encloser}\" can never be reached")
+      }
+      EscapeCollision { marker } => {
+        write!(
+          f,
+          "the escape char collides with the marker of \"{marker}\""
+        )
+      }
+      OperatorStartsLeaf { operator } => {
+        write!(f, "the marker of \"{operator}\" can start a leaf")
+      }
+    }?;
+    write!(f, " in context {:?}, e.g. {:?}", self.context, self.example)
+  }
+}
+
+struct ContextMarker<'g> {
+  id: &'g str,
+  marker: &'g str,
+}
+
+fn context_markers<'g, E: Encloser, O: Operator>(
+  context: &'g SyntaxContext<E, O>,
+) -> Vec<ContextMarker<'g>> {
+  context
+    .reader_macros()
+    .iter()
+    .map(|reader_macro| ContextMarker {
+      id: reader_macro.marker(),
+      marker: reader_macro.marker(),
+    })
+    .chain(context.enclosers().iter().map(|encloser| ContextMarker {
+      id: encloser.id_str(),
+      marker: encloser.opening_encloser_str(),
+    }))
+    .chain(context.operators().iter().map(|operator| ContextMarker {
+      id: operator.id_str(),
+      marker: operator.op_str(),
+    }))
+    .filter(|context_marker| !context_marker.marker.is_empty())
+    .collect()
+}
+
+fn starts_leaf(marker: &str) -> bool {
+  marker
+    .chars()
+    .next()
+    .is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  fn context_wrappers(&self) -> HashMap<&C, (String, String)> {
+    let mut wrappers =
+      HashMap::from([(&self.root, (String::new(), String::new()))]);
+    let mut queue = VecDeque::from([&self.root]);
+    while let Some(context_tag) = queue.pop_front() {
+      let (prefix, suffix) = wrappers[context_tag].clone();
+      for encloser in self.get_context(context_tag).enclosers() {
+        let target = self.get_encloser_context_tag(encloser);
+        if !wrappers.contains_key(target) {
+          wrappers.insert(
+            target,
+            (
+              format!("{prefix}{}", encloser.opening_encloser_str()),
+              format!("{}{suffix}", encloser.closing_encloser_str()),
+            ),
+          );
+          queue.push_back(target);
+        }
+      }
+    }
+    wrappers
+  }
+  pub fn analyze(&self) -> Vec<Ambiguity<C>> {
+    let wrappers = self.context_wrappers();
+    let mut ambiguities = vec![];
+    for (context_tag, context) in self.contexts() {
+      let (prefix, suffix) =
+        wrappers.get(context_tag).cloned().unwrap_or_default();
+      let mut push = |kind, example: String| {
+        ambiguities.push(Ambiguity {
+          context: context_tag.clone(),
+          kind,
+          example: format!("{prefix}{example}{suffix}"),
+        })
+      };
+      let markers = context_markers(context);
+      for (i, first) in markers.iter().enumerate() {
+        for second in &markers[i + 1..] {
+          let (shorter, longer, shadowed) =
+            if second.marker.starts_with(first.marker) {
+              (first, second, true)
+            } else if first.marker.starts_with(second.marker) {
+              (second, first, false)
+            } else {
+              continue;
+            };
+          push(
+            AmbiguityKind::PrefixMarker {
+              shorter: shorter.id.to_string(),
+              longer: longer.id.to_string(),
+              shadowed,
+            },
+            format!("{}x", longer.marker),
+          );
+        }
+      }
+      if let Some(escape_char) = context.escape_char() {
+        for context_marker in &markers {
+          if context_marker.marker.starts_with(escape_char) {
+            push(
+              AmbiguityKind::EscapeCollision {
+                marker: context_marker.id.to_string(),
+              },
+              context_marker.marker.to_string(),
+            );
+          }
+        }
+      }
+      for operator in context.operators() {
+        if starts_leaf(operator.op_str()) {
+          let left = if operator.left_args() > 0 { "x" } else { "" };
+          push(
+            AmbiguityKind::OperatorStartsLeaf {
+              operator: operator.id_str().to_string(),
+            },
+            format!("{left}{}y", operator.op_str()),
+          );
+        }
+      }
+      for encloser in context.enclosers() {
+        let opener = encloser.opening_encloser_str();
+        let closer = encloser.closing_encloser_str();
+        let target = self.get_context(self.get_encloser_context_tag(encloser));
+        let swallowed = closer.graphemes(true).next().is_some_and(|first| {
+          target.is_whitespace(first) || target.escape_char() == Some(first)
+        });
+        if swallowed {
+          push(
+            AmbiguityKind::SwallowedCloser {
+              encloser: encloser.id_str().to_string(),
+            },
+            format!("{opener}x{closer}"),
+          );
+          continue;
+        }
+        for target_marker in context_markers(target) {
+          if !closer.is_empty() && target_marker.marker.starts_with(closer) {
+            push(
+              AmbiguityKind::CloserIsOpener {
+                encloser: encloser.id_str().to_string(),
+                opener: target_marker.id.to_string(),
+              },
+              format!("{opener}{}", target_marker.marker),
+            );
+          }
+        }
+      }
+    }
+    ambiguities.sort_by_key(|ambiguity| {
+      (
+        format!("{:?}", ambiguity.context),
+        ambiguity.example.clone(),
+      )
+    });
+    ambiguities
+  }
+}
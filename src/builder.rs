@@ -0,0 +1,340 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use crate::{
+  reader_macro::ReaderMacro,
+  syntax::{ContextKind, SyntaxGraphError},
+  Encloser, Operator, SyntaxContext, SyntaxGraph,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GraphMember<T, C> {
+  pub member: T,
+  pub target: C,
+  pub within: Vec<C>,
+}
+
+pub trait GraphMembers<C>: Sized {
+  fn graph_members() -> Vec<GraphMember<Self, C>>;
+}
+
+#[derive(Clone, Debug)]
+pub struct ContextBuilder<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub(crate) kind: ContextKind,
+  pub(crate) whitespace_chars: Vec<String>,
+  pub(crate) escape_char: Option<String>,
+  pub(crate) enclosers: Vec<(E, C)>,
+  pub(crate) operators: Vec<(O, C)>,
+  pub(crate) reader_macros: Vec<ReaderMacro<E, O>>,
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator> Default
+  for ContextBuilder<C, E, O>
+{
+  fn default() -> Self {
+    Self {
+      kind: ContextKind::Code,
+      whitespace_chars: vec![],
+      escape_char: None,
+      enclosers: vec![],
+      operators: vec![],
+      reader_macros: vec![],
+    }
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  ContextBuilder<C, E, O>
+{
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn with_kind(mut self, kind: ContextKind) -> Self {
+    self.kind = kind;
+    self
+  }
+  pub fn with_whitespace(mut self, whitespace_char: impl Into<String>) -> Self {
+    self.whitespace_chars.push(whitespace_char.into());
+    self
+  }
+  pub fn with_whitespace_chars<S: Into<String>>(
+    mut self,
+    whitespace_chars: impl IntoIterator<Item = S>,
+  ) -> Self {
+    self
+      .whitespace_chars
+      .extend(whitespace_chars.into_iter().map(Into::into));
+    self
+  }
+  pub fn with_escape_char(mut self, escape_char: impl Into<String>) -> Self {
+    self.escape_char = Some(escape_char.into());
+    self
+  }
+  pub fn with_encloser(mut self, encloser: E, target_context: C) -> Self {
+    self.enclosers.push((encloser, target_context));
+    self
+  }
+  pub fn with_operator(mut self, operator: O, target_context: C) -> Self {
+    self.operators.push((operator, target_context));
+    self
+  }
+  pub fn without_whitespace(mut self, whitespace_char: &str) -> Self {
+    self
+      .whitespace_chars
+      .retain(|existing| existing != whitespace_char);
+    self
+  }
+  pub fn without_escape_char(mut self) -> Self {
+    self.escape_char = None;
+    self
+  }
+  pub fn without_encloser(mut self, encloser: &E) -> Self {
+    self.enclosers.retain(|(existing, _)| existing != encloser);
+    self
+  }
+  pub fn without_operator(mut self, operator: &O) -> Self {
+    self.operators.retain(|(existing, _)| existing != operator);
+    self
+  }
+  pub fn without_reader_macro(mut self, marker: &str) -> Self {
+    self
+      .reader_macros
+      .retain(|reader_macro| reader_macro.marker() != marker);
+    self
+  }
+  pub fn with_reader_macro(mut self, reader_macro: ReaderMacro<E, O>) -> Self {
+    self.reader_macros.push(reader_macro);
+    self
+  }
+  pub fn with_graph_members(mut self, context_tag: &C) -> Self
+  where
+    E: GraphMembers<C>,
+    O: GraphMembers<C>,
+  {
+    for GraphMember {
+      member,
+      target,
+      within,
+    } in E::graph_members()
+    {
+      if within.contains(context_tag) {
+        self.enclosers.push((member, target));
+      }
+    }
+    for GraphMember {
+      member,
+      target,
+      within,
+    } in O::graph_members()
+    {
+      if within.contains(context_tag) {
+        self.operators.push((member, target));
+      }
+    }
+    self
+  }
+}
+
+fn dedup_inherited<T: PartialEq>(members: &mut Vec<T>, inherited: &[T]) {
+  let mut kept: Vec<T> = vec![];
+  for member in members.drain(..) {
+    if !(inherited.contains(&member) && kept.contains(&member)) {
+      kept.push(member);
+    }
+  }
+  *members = kept;
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxGraphBuilder<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub(crate) root: C,
+  pub(crate) contexts: Vec<(C, ContextBuilder<C, E, O>)>,
+  pub(crate) errors: Vec<SyntaxGraphError<C>>,
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraphBuilder<C, E, O>
+{
+  pub fn new(root: C) -> Self {
+    Self {
+      root,
+      contexts: vec![],
+      errors: vec![],
+    }
+  }
+
+  pub fn with_context<F>(mut self, context_tag: C, build_context: F) -> Self
+  where
+    F: FnOnce(ContextBuilder<C, E, O>) -> ContextBuilder<C, E, O>,
+  {
+    self
+      .contexts
+      .push((context_tag, build_context(ContextBuilder::new())));
+    self
+  }
+  pub fn with_context_extending<F>(
+    mut self,
+    context_tag: C,
+    parent_tag: C,
+    build_context: F,
+  ) -> Self
+  where
+    F: FnOnce(ContextBuilder<C, E, O>) -> ContextBuilder<C, E, O>,
+  {
+    let parent = self
+      .contexts
+      .iter()
+      .rev()
+      .find(|(existing, _)| *existing == parent_tag)
+      .map(|(_, parent)| parent.clone());
+    let parent = parent.unwrap_or_else(|| {
+      self.errors.push(SyntaxGraphError::UndefinedParentContext {
+        context: context_tag.clone(),
+        parent: parent_tag,
+      });
+      ContextBuilder::new()
+    });
+    let (inherited_enclosers, inherited_operators) =
+      (parent.enclosers.clone(), parent.operators.clone());
+    let mut context = build_context(parent);
+    dedup_inherited(&mut context.enclosers, &inherited_enclosers);
+    dedup_inherited(&mut context.operators, &inherited_operators);
+    self.contexts.push((context_tag, context));
+    self
+  }
+  pub fn update_context<F>(mut self, context_tag: C, update_context: F) -> Self
+  where
+    F: FnOnce(ContextBuilder<C, E, O>) -> ContextBuilder<C, E, O>,
+  {
+    match self
+      .contexts
+      .iter_mut()
+      .rev()
+      .find(|(existing, _)| *existing == context_tag)
+    {
+      Some((_, context)) => {
+        *context = update_context(std::mem::take(context));
+        self
+      }
+      None => self.with_context(context_tag, update_context),
+    }
+  }
+  pub fn build(self) -> Result<SyntaxGraph<C, E, O>, Vec<SyntaxGraphError<C>>> {
+    let mut errors = self.errors;
+    let mut contexts = HashMap::new();
+    let mut encloser_contexts: HashMap<E, C> = HashMap::new();
+    let mut operator_contexts: HashMap<O, C> = HashMap::new();
+    for (context_tag, context) in self.contexts {
+      if contexts.contains_key(&context_tag) {
+        errors.push(SyntaxGraphError::DuplicateContext(context_tag));
+        continue;
+      }
+      for (encloser, target) in &context.enclosers {
+        match encloser_contexts.get(encloser) {
+          Some(first) if first != target => {
+            errors.push(SyntaxGraphError::ConflictingContexts {
+              id: encloser.id_str().to_string(),
+              first: first.clone(),
+              second: target.clone(),
+            })
+          }
+          Some(_) => {}
+          None => {
+            encloser_contexts.insert(encloser.clone(), target.clone());
+          }
+        }
+      }
+      for (operator, target) in &context.operators {
+        match operator_contexts.get(operator) {
+          Some(first) if first != target => {
+            errors.push(SyntaxGraphError::ConflictingContexts {
+              id: operator.id_str().to_string(),
+              first: first.clone(),
+              second: target.clone(),
+            })
+          }
+          Some(_) => {}
+          None => {
+            operator_contexts.insert(operator.clone(), target.clone());
+          }
+        }
+      }
+      let syntax_context = SyntaxContext::new(
+        context
+          .enclosers
+          .into_iter()
+          .map(|(encloser, _)| encloser)
+          .collect(),
+        context
+          .operators
+          .into_iter()
+          .map(|(operator, _)| operator)
+          .collect(),
+        context.escape_char,
+        context.whitespace_chars,
+      );
+      contexts.insert(
+        context_tag,
+        context.reader_macros.into_iter().fold(
+          syntax_context.with_kind(context.kind),
+          SyntaxContext::with_reader_macro,
+        ),
+      );
+    }
+    let graph = SyntaxGraph::new(
+      self.root,
+      contexts,
+      encloser_contexts,
+      operator_contexts,
+    );
+    if let Err(validation_errors) = graph.validate() {
+      errors.extend(validation_errors);
+    }
+    if errors.is_empty() {
+      Ok(graph)
+    } else {
+      Err(errors)
+    }
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  pub fn to_builder(&self) -> SyntaxGraphBuilder<C, E, O> {
+    self.sorted_contexts().into_iter().fold(
+      SyntaxGraphBuilder::new(self.root().clone()),
+      |builder, (context_tag, context)| {
+        builder.with_context(context_tag.clone(), |_| ContextBuilder {
+          kind: context.kind(),
+          whitespace_chars: context.whitespace_chars().to_vec(),
+          escape_char: context.escape_char().map(str::to_string),
+          enclosers: context
+            .enclosers()
+            .iter()
+            .map(|encloser| {
+              let target = self.get_encloser_context_tag(encloser);
+              (encloser.clone(), target.clone())
+            })
+            .collect(),
+          operators: context
+            .operators()
+            .iter()
+            .map(|operator| {
+              let target = self.get_operator_context_tag(operator);
+              (operator.clone(), target.clone())
+            })
+            .collect(),
+          reader_macros: context.reader_macros().to_vec(),
+        })
+      },
+    )
+  }
+}
@@ -0,0 +1,283 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::Debug,
+  hash::Hash,
+};
+
+use crate::{
+  builder::ContextBuilder,
+  syntax::{EncloserOrOperator, SyntaxGraphError},
+  Encloser, Operator, SyntaxGraph,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClashResolution {
+  KeepBase,
+  KeepOverlay,
+  Reject,
+}
+
+type Member<E, O> = EncloserOrOperator<E, O>;
+
+fn marker<E: Encloser, O: Operator>(member: &Member<E, O>) -> &str {
+  match member {
+    EncloserOrOperator::Encloser(encloser) => encloser.opening_encloser_str(),
+    EncloserOrOperator::Operator(operator) => operator.op_str(),
+  }
+}
+
+fn graph_members<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_graph: &SyntaxGraph<C, E, O>,
+) -> Vec<(Member<E, O>, C)> {
+  let mut members = syntax_graph
+    .enclosers()
+    .map(|encloser| {
+      (
+        EncloserOrOperator::Encloser(encloser.clone()),
+        syntax_graph.get_encloser_context_tag(encloser).clone(),
+      )
+    })
+    .chain(syntax_graph.operators().map(|operator| {
+      (
+        EncloserOrOperator::Operator(operator.clone()),
+        syntax_graph.get_operator_context_tag(operator).clone(),
+      )
+    }))
+    .collect::<Vec<_>>();
+  members.sort_by(|(a, _), (b, _)| a.id_str().cmp(b.id_str()));
+  members
+}
+
+fn context_members<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  context: &ContextBuilder<C, E, O>,
+) -> Vec<Member<E, O>> {
+  context
+    .enclosers
+    .iter()
+    .map(|(encloser, _)| EncloserOrOperator::Encloser(encloser.clone()))
+    .chain(
+      context
+        .operators
+        .iter()
+        .map(|(operator, _)| EncloserOrOperator::Operator(operator.clone())),
+    )
+    .collect()
+}
+
+struct Clashes<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  resolution: ClashResolution,
+  dropped_base: HashSet<Member<E, O>>,
+  dropped_overlay: HashSet<Member<E, O>>,
+  targets: HashMap<Member<E, O>, C>,
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  Clashes<C, E, O>
+{
+  fn clash(&mut self, base: &Member<E, O>, overlay: &Member<E, O>) {
+    match self.resolution {
+      ClashResolution::KeepBase => {
+        self.dropped_overlay.insert(overlay.clone());
+      }
+      ClashResolution::KeepOverlay => {
+        self.dropped_base.insert(base.clone());
+      }
+      ClashResolution::Reject => {}
+    }
+  }
+  fn resolve(
+    &self,
+    mut context: ContextBuilder<C, E, O>,
+    dropped: &HashSet<Member<E, O>>,
+  ) -> ContextBuilder<C, E, O> {
+    let target = |member: Member<E, O>, target: C| {
+      self.targets.get(&member).cloned().unwrap_or(target)
+    };
+    context.enclosers = context
+      .enclosers
+      .into_iter()
+      .filter(|(encloser, _)| {
+        !dropped.contains(&EncloserOrOperator::Encloser(encloser.clone()))
+      })
+      .map(|(encloser, context_tag)| {
+        let member = EncloserOrOperator::Encloser(encloser.clone());
+        (encloser, target(member, context_tag))
+      })
+      .collect();
+    context.operators = context
+      .operators
+      .into_iter()
+      .filter(|(operator, _)| {
+        !dropped.contains(&EncloserOrOperator::Operator(operator.clone()))
+      })
+      .map(|(operator, context_tag)| {
+        let member = EncloserOrOperator::Operator(operator.clone());
+        (operator, target(member, context_tag))
+      })
+      .collect();
+    context
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  pub fn merge(
+    &self,
+    overlay: &SyntaxGraph<C, E, O>,
+    resolution: ClashResolution,
+  ) -> Result<SyntaxGraph<C, E, O>, Vec<SyntaxGraphError<C>>> {
+    let mut clashes = Clashes {
+      resolution,
+      dropped_base: HashSet::new(),
+      dropped_overlay: HashSet::new(),
+      targets: HashMap::new(),
+    };
+    let mut merged = self.to_builder();
+    let base_members = graph_members(self);
+    for (overlay_member, overlay_target) in graph_members(overlay) {
+      for (base_member, base_target) in &base_members {
+        if *base_member == overlay_member {
+          let target = match resolution {
+            ClashResolution::KeepBase => base_target,
+            ClashResolution::KeepOverlay => &overlay_target,
+            ClashResolution::Reject => {
+              if *base_target != overlay_target {
+                merged.errors.push(SyntaxGraphError::ConflictingContexts {
+                  id: base_member.id_str().to_string(),
+                  first: base_target.clone(),
+                  second: overlay_target.clone(),
+                });
+              }
+              continue;
+            }
+          };
+          clashes
+            .targets
+            .insert(overlay_member.clone(), target.clone());
+        } else if base_member.id_str() == overlay_member.id_str() {
+          clashes.clash(base_member, &overlay_member);
+        }
+      }
+    }
+    let overlay_contexts = overlay.to_builder().contexts;
+    for (context_tag, overlay_context) in &overlay_contexts {
+      let Some((_, base_context)) = merged
+        .contexts
+        .iter()
+        .find(|(existing, _)| existing == context_tag)
+      else {
+        continue;
+      };
+      for overlay_member in context_members(overlay_context) {
+        for base_member in context_members(base_context) {
+          if base_member != overlay_member
+            && base_member.id_str() != overlay_member.id_str()
+            && marker(&base_member) == marker(&overlay_member)
+          {
+            clashes.clash(&base_member, &overlay_member);
+          }
+        }
+      }
+    }
+    merged.contexts = merged
+      .contexts
+      .into_iter()
+      .map(|(context_tag, context)| {
+        (context_tag, clashes.resolve(context, &clashes.dropped_base))
+      })
+      .collect();
+    for (context_tag, overlay_context) in overlay_contexts {
+      let overlay_context =
+        clashes.resolve(overlay_context, &clashes.dropped_overlay);
+      let Some((_, context)) = merged
+        .contexts
+        .iter_mut()
+        .find(|(existing, _)| *existing == context_tag)
+      else {
+        merged.contexts.push((context_tag, overlay_context));
+        continue;
+      };
+      for whitespace_char in overlay_context.whitespace_chars {
+        if !context.whitespace_chars.contains(&whitespace_char) {
+          context.whitespace_chars.push(whitespace_char);
+        }
+      }
+      match (&context.escape_char, overlay_context.escape_char) {
+        (Some(base), Some(overlay)) if *base != overlay => match resolution {
+          ClashResolution::KeepBase => {}
+          ClashResolution::KeepOverlay => context.escape_char = Some(overlay),
+          ClashResolution::Reject => {
+            merged
+              .errors
+              .push(SyntaxGraphError::ConflictingEscapeChars {
+                context: context_tag.clone(),
+              })
+          }
+        },
+        (None, overlay) => context.escape_char = overlay,
+        _ => {}
+      }
+      if context.kind != overlay_context.kind {
+        match resolution {
+          ClashResolution::KeepBase => {}
+          ClashResolution::KeepOverlay => context.kind = overlay_context.kind,
+          ClashResolution::Reject => {
+            merged
+              .errors
+              .push(SyntaxGraphError::ConflictingContextKinds {
+                context: context_tag.clone(),
+              })
+          }
+        }
+      }
+      for (encloser, target) in overlay_context.enclosers {
+        if !context
+          .enclosers
+          .iter()
+          .any(|(existing, _)| *existing == encloser)
+        {
+          context.enclosers.push((encloser, target));
+        }
+      }
+      for (operator, target) in overlay_context.operators {
+        if !context
+          .operators
+          .iter()
+          .any(|(existing, _)| *existing == operator)
+        {
+          context.operators.push((operator, target));
+        }
+      }
+      for reader_macro in overlay_context.reader_macros {
+        match context
+          .reader_macros
+          .iter()
+          .position(|existing| existing.marker() == reader_macro.marker())
+        {
+          Some(position) => match resolution {
+            ClashResolution::KeepBase => {}
+            ClashResolution::KeepOverlay => {
+              context.reader_macros[position] = reader_macro
+            }
+            ClashResolution::Reject => context.reader_macros.push(reader_macro),
+          },
+          None => context.reader_macros.push(reader_macro),
+        }
+      }
+    }
+    merged.build()
+  }
+}
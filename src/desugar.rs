@@ -0,0 +1,214 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::{
+  Encloser, Operator, RawSexp, Sexp, SyntaxContext, SyntaxGraph, SyntaxTree,
+};
+
+pub type TaggedSexp = Sexp<(), String>;
+
+pub trait DesugarStrategy {
+  type InnerData: Clone + PartialEq + Eq + Debug;
+  fn desugar<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    E: Encloser,
+    O: Operator,
+  >(
+    &self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    tree: &SyntaxTree<E, O>,
+  ) -> Sexp<(), Self::InnerData>;
+  fn tag<'s, E: Encloser, O: Operator>(
+    &self,
+    context: &SyntaxContext<E, O>,
+    inner_data: &'s Self::InnerData,
+    children: &'s [Sexp<(), Self::InnerData>],
+  ) -> Option<(&'s str, usize)>;
+  fn resugar_leaf(&self, leaf: &str) -> String {
+    leaf.to_string()
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefixTag;
+
+impl DesugarStrategy for PrefixTag {
+  type InnerData = ();
+  fn desugar<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    E: Encloser,
+    O: Operator,
+  >(
+    &self,
+    _syntax_graph: &SyntaxGraph<C, E, O>,
+    tree: &SyntaxTree<E, O>,
+  ) -> RawSexp {
+    tree.clone().into()
+  }
+  fn tag<'s, E: Encloser, O: Operator>(
+    &self,
+    context: &SyntaxContext<E, O>,
+    _inner_data: &'s (),
+    children: &'s [RawSexp],
+  ) -> Option<(&'s str, usize)> {
+    match children.first() {
+      Some(Sexp::Leaf((), head))
+        if !head.is_empty()
+          && (context
+            .enclosers()
+            .iter()
+            .any(|encloser| encloser.id_str() == head)
+            || context
+              .operators()
+              .iter()
+              .any(|operator| operator.id_str() == head)) =>
+      {
+        Some((head, 1))
+      }
+      _ => Some(("", 0)),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WrappedTag {
+  escape: String,
+}
+
+impl Default for WrappedTag {
+  fn default() -> Self {
+    Self::new("\\")
+  }
+}
+
+impl WrappedTag {
+  pub fn new(escape: impl Into<String>) -> Self {
+    Self {
+      escape: escape.into(),
+    }
+  }
+  pub fn escape(&self) -> &str {
+    &self.escape
+  }
+  fn escape_tag(&self, id: &str) -> String {
+    if id.is_empty() || id.starts_with(&self.escape) {
+      format!("{}{id}", self.escape)
+    } else {
+      id.to_string()
+    }
+  }
+  fn desugar_inner<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    E: Encloser,
+    O: Operator,
+  >(
+    &self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    tree: &SyntaxTree<E, O>,
+  ) -> RawSexp {
+    match tree {
+      Sexp::Leaf((), leaf) => {
+        let tag_like = leaf.starts_with(&self.escape)
+          || syntax_graph
+            .enclosers()
+            .any(|encloser| encloser.id_str() == leaf)
+          || syntax_graph
+            .operators()
+            .any(|operator| operator.id_str() == leaf);
+        if tag_like {
+          RawSexp::leaf(format!("{}{leaf}", self.escape))
+        } else {
+          RawSexp::leaf(leaf.clone())
+        }
+      }
+      Sexp::Inner(encloser_or_operator, children) => RawSexp::inner(
+        std::iter::once(RawSexp::leaf(
+          self.escape_tag(encloser_or_operator.id_str()),
+        ))
+        .chain(
+          children
+            .iter()
+            .map(|child| self.desugar_inner(syntax_graph, child)),
+        )
+        .collect(),
+      ),
+    }
+  }
+}
+
+impl DesugarStrategy for WrappedTag {
+  type InnerData = ();
+  fn desugar<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    E: Encloser,
+    O: Operator,
+  >(
+    &self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    tree: &SyntaxTree<E, O>,
+  ) -> RawSexp {
+    self.desugar_inner(syntax_graph, tree)
+  }
+  fn tag<'s, E: Encloser, O: Operator>(
+    &self,
+    _context: &SyntaxContext<E, O>,
+    _inner_data: &'s (),
+    children: &'s [RawSexp],
+  ) -> Option<(&'s str, usize)> {
+    match children.first() {
+      Some(Sexp::Leaf((), head)) => {
+        Some((head.strip_prefix(&self.escape).unwrap_or(head), 1))
+      }
+      _ => None,
+    }
+  }
+  fn resugar_leaf(&self, leaf: &str) -> String {
+    leaf.strip_prefix(&self.escape).unwrap_or(leaf).to_string()
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InnerTag;
+
+impl DesugarStrategy for InnerTag {
+  type InnerData = String;
+  fn desugar<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    E: Encloser,
+    O: Operator,
+  >(
+    &self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    tree: &SyntaxTree<E, O>,
+  ) -> TaggedSexp {
+    match tree {
+      Sexp::Leaf((), leaf) => Sexp::Leaf((), leaf.clone()),
+      Sexp::Inner(encloser_or_operator, children) => Sexp::Inner(
+        encloser_or_operator.id_str().to_string(),
+        children
+          .iter()
+          .map(|child| self.desugar(syntax_graph, child))
+          .collect(),
+      ),
+    }
+  }
+  fn tag<'s, E: Encloser, O: Operator>(
+    &self,
+    _context: &SyntaxContext<E, O>,
+    inner_data: &'s String,
+    _children: &'s [TaggedSexp],
+  ) -> Option<(&'s str, usize)> {
+    Some((inner_data, 0))
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  pub fn desugar_with<S: DesugarStrategy>(
+    &self,
+    strategy: &S,
+    tree: &SyntaxTree<E, O>,
+  ) -> Sexp<(), S::InnerData> {
+    strategy.desugar(self, tree)
+  }
+}
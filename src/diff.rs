@@ -0,0 +1,250 @@
+use std::{fmt::Debug, hash::Hash, ops::Range};
+
+use crate::{document::Document, Encloser, Operator, Sexp, SyntaxTree};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TreeChange {
+  Inserted {
+    new_path: Vec<usize>,
+  },
+  Deleted {
+    old_path: Vec<usize>,
+  },
+  Moved {
+    old_path: Vec<usize>,
+    new_path: Vec<usize>,
+  },
+  Changed {
+    old_path: Vec<usize>,
+    new_path: Vec<usize>,
+  },
+}
+
+impl TreeChange {
+  pub fn old_path(&self) -> Option<&[usize]> {
+    match self {
+      TreeChange::Inserted { .. } => None,
+      TreeChange::Deleted { old_path }
+      | TreeChange::Moved { old_path, .. }
+      | TreeChange::Changed { old_path, .. } => Some(old_path),
+    }
+  }
+  pub fn new_path(&self) -> Option<&[usize]> {
+    match self {
+      TreeChange::Deleted { .. } => None,
+      TreeChange::Inserted { new_path }
+      | TreeChange::Moved { new_path, .. }
+      | TreeChange::Changed { new_path, .. } => Some(new_path),
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocumentChange {
+  pub change: TreeChange,
+  pub old_range: Option<Range<usize>>,
+  pub new_range: Option<Range<usize>>,
+}
+
+pub(crate) fn longest_common_subsequence<T: PartialEq>(
+  old: &[T],
+  new: &[T],
+) -> Vec<(usize, usize)> {
+  let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lengths[i][j] = if old[i] == new[j] {
+        lengths[i + 1][j + 1] + 1
+      } else {
+        lengths[i + 1][j].max(lengths[i][j + 1])
+      };
+    }
+  }
+  let mut pairs = vec![];
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() && j < new.len() {
+    if old[i] == new[j] {
+      pairs.push((i, j));
+      i += 1;
+      j += 1;
+    } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+      i += 1;
+    } else {
+      j += 1;
+    }
+  }
+  pairs
+}
+
+fn with_index(path: &[usize], index: usize) -> Vec<usize> {
+  let mut path = path.to_vec();
+  path.push(index);
+  path
+}
+
+pub(crate) fn similar<E: Encloser, O: Operator>(
+  old: &SyntaxTree<E, O>,
+  new: &SyntaxTree<E, O>,
+) -> bool {
+  match (old, new) {
+    (Sexp::Leaf(_, _), Sexp::Leaf(_, _)) => true,
+    (
+      Sexp::Inner(old_tag, old_children),
+      Sexp::Inner(new_tag, new_children),
+    ) => {
+      old_tag == new_tag
+        && (old_children.is_empty()
+          || new_children.is_empty()
+          || old_children
+            .iter()
+            .any(|child| new_children.contains(child)))
+    }
+    _ => false,
+  }
+}
+
+struct TreeDiff<'t, E: Encloser, O: Operator> {
+  changes: Vec<TreeChange>,
+  deleted: Vec<(Vec<usize>, &'t SyntaxTree<E, O>)>,
+  inserted: Vec<(Vec<usize>, &'t SyntaxTree<E, O>)>,
+}
+
+impl<'t, E: Encloser, O: Operator> TreeDiff<'t, E, O> {
+  fn diff_node(
+    &mut self,
+    old: &'t SyntaxTree<E, O>,
+    new: &'t SyntaxTree<E, O>,
+    old_path: Vec<usize>,
+    new_path: Vec<usize>,
+  ) {
+    match (old, new) {
+      (Sexp::Inner(_, old_children), Sexp::Inner(_, new_children)) => {
+        self.diff_sequences(old_children, new_children, &old_path, &new_path)
+      }
+      _ => self
+        .changes
+        .push(TreeChange::Changed { old_path, new_path }),
+    }
+  }
+  fn diff_sequences(
+    &mut self,
+    old: &'t [SyntaxTree<E, O>],
+    new: &'t [SyntaxTree<E, O>],
+    old_path: &[usize],
+    new_path: &[usize],
+  ) {
+    let common = longest_common_subsequence(old, new);
+    let mut unmatched_old = (0..old.len())
+      .filter(|i| !common.iter().any(|(old_index, _)| old_index == i))
+      .collect::<Vec<_>>();
+    let mut unmatched_new = (0..new.len())
+      .filter(|j| !common.iter().any(|(_, new_index)| new_index == j))
+      .map(Some)
+      .collect::<Vec<_>>();
+    unmatched_old.retain(|&old_index| {
+      let moved_to = unmatched_new
+        .iter_mut()
+        .find(|new_index| new_index.is_some_and(|j| new[j] == old[old_index]))
+        .and_then(Option::take);
+      if let Some(new_index) = moved_to {
+        self.changes.push(TreeChange::Moved {
+          old_path: with_index(old_path, old_index),
+          new_path: with_index(new_path, new_index),
+        });
+      }
+      moved_to.is_none()
+    });
+    for old_index in unmatched_old {
+      let paired = unmatched_new
+        .iter_mut()
+        .find(|new_index| {
+          new_index.is_some_and(|j| similar(&old[old_index], &new[j]))
+        })
+        .and_then(Option::take);
+      match paired {
+        Some(new_index) => self.diff_node(
+          &old[old_index],
+          &new[new_index],
+          with_index(old_path, old_index),
+          with_index(new_path, new_index),
+        ),
+        None => self
+          .deleted
+          .push((with_index(old_path, old_index), &old[old_index])),
+      }
+    }
+    self.inserted.extend(
+      unmatched_new
+        .into_iter()
+        .flatten()
+        .map(|new_index| (with_index(new_path, new_index), &new[new_index])),
+    );
+  }
+  fn finish(mut self) -> Vec<TreeChange> {
+    let mut inserted = self.inserted.into_iter().map(Some).collect::<Vec<_>>();
+    for (old_path, deleted_tree) in self.deleted {
+      let moved_to = inserted.iter_mut().find(|inserted| {
+        inserted
+          .as_ref()
+          .is_some_and(|(_, inserted_tree)| *inserted_tree == deleted_tree)
+      });
+      match moved_to.and_then(Option::take) {
+        Some((new_path, _)) => {
+          self.changes.push(TreeChange::Moved { old_path, new_path })
+        }
+        None => self.changes.push(TreeChange::Deleted { old_path }),
+      }
+    }
+    self.changes.extend(
+      inserted
+        .into_iter()
+        .flatten()
+        .map(|(new_path, _)| TreeChange::Inserted { new_path }),
+    );
+    self.changes
+  }
+}
+
+pub fn diff<E: Encloser, O: Operator>(
+  old: &[SyntaxTree<E, O>],
+  new: &[SyntaxTree<E, O>],
+) -> Vec<TreeChange> {
+  let mut tree_diff = TreeDiff {
+    changes: vec![],
+    deleted: vec![],
+    inserted: vec![],
+  };
+  tree_diff.diff_sequences(old, new, &[], &[]);
+  tree_diff.finish()
+}
+
+pub fn diff_documents<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  old: &Document<'_, C, E, O>,
+  new: &Document<'_, C, E, O>,
+) -> Vec<DocumentChange> {
+  let syntax_trees = |document: &Document<'_, C, E, O>| {
+    document
+      .syntax_trees()
+      .iter()
+      .map(|tree| SyntaxTree::from(tree.clone()))
+      .collect::<Vec<_>>()
+  };
+  diff(&syntax_trees(old), &syntax_trees(new))
+    .into_iter()
+    .map(|change| DocumentChange {
+      old_range: change
+        .old_path()
+        .and_then(|path| old.get_subtree(path).ok())
+        .map(|tree| tree.range().clone()),
+      new_range: change
+        .new_path()
+        .and_then(|path| new.get_subtree(path).ok())
+        .map(|tree| tree.range().clone()),
+      change,
+    })
+    .collect()
+}
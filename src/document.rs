@@ -1,9 +1,21 @@
-use std::{fmt::Debug, hash::Hash, ops::Range};
+use std::{borrow::Cow, fmt::Debug, hash::Hash, ops::Range, sync::OnceLock};
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
-  ast::InvalidTreePath, DocumentSyntaxTree, Encloser, Operator, ParseError,
-  Parser, Sexp, SyntaxGraph,
+  ast::InvalidTreePath,
+  diff::{diff_documents, DocumentChange},
+  indent::IndentRules,
+  markers::{Marker, MarkerScan, ScannedText, Token},
+  outline::{Outline, OutlineSymbol},
+  parinfer::{
+    parinfer_in_regions, ParinferError, ParinferMode, ParinferResult,
+  },
+  parser::TopLevelReader,
+  query::{DocumentMatch, Pattern},
+  rewrite::{Rewrite, RewriteMatch},
+  syntax::EncloserOrOperator,
+  DocumentSyntaxTree, Encloser, Operator, ParseError, Parser, Sexp,
+  SyntaxGraph,
 };
 
 pub struct Document<
@@ -12,11 +24,25 @@ pub struct Document<
   E: Encloser,
   O: Operator,
 > {
-  pub text: &'t str,
+  pub text: Cow<'t, str>,
   grapheme_indeces: Vec<usize>,
   newline_indeces: Vec<usize>,
   syntax_graph: SyntaxGraph<C, E, O>,
+  syntax_regions: Vec<SyntaxRegion<C, E, O>>,
   syntax_trees: Vec<DocumentSyntaxTree<E, O>>,
+  resume_indeces: Vec<usize>,
+  version: usize,
+  scanned_text: OnceLock<ScannedText<C, E, O>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxRegion<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub start: usize,
+  pub syntax_graph: SyntaxGraph<C, E, O>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -25,37 +51,116 @@ pub struct InvalidDocumentIndex;
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct InvalidDocumentCharPos;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+  InvalidIndex(InvalidDocumentIndex),
+  Parse(ParseError),
+}
+
+impl From<InvalidDocumentIndex> for EditError {
+  fn from(error: InvalidDocumentIndex) -> Self {
+    Self::InvalidIndex(error)
+  }
+}
+
+impl From<ParseError> for EditError {
+  fn from(error: ParseError) -> Self {
+    Self::Parse(error)
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FoldingRange<E: Encloser> {
+  pub range: Range<usize>,
+  pub start_row: usize,
+  pub end_row: usize,
+  pub encloser: E,
+}
+
+pub type OwnedDocument<C, E, O> = Document<'static, C, E, O>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+  pub range: Range<usize>,
+  pub text: String,
+}
+
+impl TextEdit {
+  pub fn new(range: Range<usize>, text: impl Into<String>) -> Self {
+    Self {
+      range,
+      text: text.into(),
+    }
+  }
+}
+
+fn grapheme_and_newline_indeces(
+  text: &str,
+  offset: usize,
+) -> (Vec<usize>, Vec<usize>) {
+  text.grapheme_indices(true).fold(
+    (vec![], vec![]),
+    |(mut grapheme_indeces, mut newline_indeces), (i, char)| {
+      grapheme_indeces.push(offset + i);
+      if char == "\n" {
+        newline_indeces.push(offset + i);
+      }
+      (grapheme_indeces, newline_indeces)
+    },
+  )
+}
+
+fn read_syntax_trees<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  reader: &mut TopLevelReader<E, O>,
+  syntax_graph: &SyntaxGraph<C, E, O>,
+  text: &str,
+  region_end: Option<usize>,
+  syntax_trees: &mut Vec<DocumentSyntaxTree<E, O>>,
+  resume_indeces: &mut Vec<usize>,
+) -> Result<(), ParseError> {
+  while let Some(tree) = reader.read_next(syntax_graph, text)? {
+    if region_end.is_some_and(|end| tree.range().start >= end) {
+      break;
+    }
+    syntax_trees.push(tree);
+    resume_indeces.push(reader.resume_index());
+  }
+  Ok(())
+}
+
 impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
   TryFrom<Parser<'t, C, E, O>> for Document<'t, C, E, O>
 {
   type Error = ParseError;
 
   fn try_from(mut parser: Parser<'t, C, E, O>) -> Result<Self, ParseError> {
-    parser
-      .read_all()
-      .into_iter()
-      .collect::<Result<Vec<_>, ParseError>>()
-      .map(|syntax_trees| {
-        let (mut grapheme_indeces, newline_indeces) =
-          parser.text.grapheme_indices(true).fold(
-            (vec![], vec![]),
-            |(mut grapheme_indeces, mut newline_indeces), (i, char)| {
-              grapheme_indeces.push(i);
-              if char == "\n" {
-                newline_indeces.push(i);
-              }
-              (grapheme_indeces, newline_indeces)
-            },
-          );
-        grapheme_indeces.push(parser.text.len());
-        Self {
-          text: parser.text,
-          grapheme_indeces,
-          newline_indeces,
-          syntax_graph: parser.syntax_graph,
-          syntax_trees,
-        }
-      })
+    let (mut syntax_trees, mut resume_indeces) = (vec![], vec![]);
+    read_syntax_trees(
+      &mut parser.reader,
+      &parser.syntax_graph,
+      parser.text,
+      None,
+      &mut syntax_trees,
+      &mut resume_indeces,
+    )?;
+    let (mut grapheme_indeces, newline_indeces) =
+      grapheme_and_newline_indeces(parser.text, 0);
+    grapheme_indeces.push(parser.text.len());
+    Ok(Self {
+      text: Cow::Borrowed(parser.text),
+      grapheme_indeces,
+      newline_indeces,
+      syntax_graph: parser.syntax_graph,
+      syntax_regions: vec![],
+      syntax_trees,
+      resume_indeces,
+      version: 0,
+      scanned_text: OnceLock::new(),
+    })
   }
 }
 
@@ -68,6 +173,300 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
   ) -> Result<Self, ParseError> {
     Parser::new(syntax_graph, text).try_into()
   }
+  pub fn from_string_with_syntax(
+    syntax_graph: SyntaxGraph<C, E, O>,
+    text: String,
+  ) -> Result<OwnedDocument<C, E, O>, ParseError> {
+    let (mut syntax_trees, mut resume_indeces) = (vec![], vec![]);
+    read_syntax_trees(
+      &mut TopLevelReader::new(&syntax_graph, 0),
+      &syntax_graph,
+      &text,
+      None,
+      &mut syntax_trees,
+      &mut resume_indeces,
+    )?;
+    let (mut grapheme_indeces, newline_indeces) =
+      grapheme_and_newline_indeces(&text, 0);
+    grapheme_indeces.push(text.len());
+    Ok(Document {
+      text: Cow::Owned(text),
+      grapheme_indeces,
+      newline_indeces,
+      syntax_graph,
+      syntax_regions: vec![],
+      syntax_trees,
+      resume_indeces,
+      version: 0,
+      scanned_text: OnceLock::new(),
+    })
+  }
+  pub fn into_owned(self) -> OwnedDocument<C, E, O> {
+    Document {
+      text: Cow::Owned(self.text.into_owned()),
+      grapheme_indeces: self.grapheme_indeces,
+      newline_indeces: self.newline_indeces,
+      syntax_graph: self.syntax_graph,
+      syntax_regions: self.syntax_regions,
+      syntax_trees: self.syntax_trees,
+      resume_indeces: self.resume_indeces,
+      version: self.version,
+      scanned_text: self.scanned_text,
+    }
+  }
+  pub fn version(&self) -> usize {
+    self.version
+  }
+  fn bump_version(&mut self) {
+    self.version += 1;
+    self.scanned_text = OnceLock::new();
+  }
+  pub fn syntax_graph(&self) -> &SyntaxGraph<C, E, O> {
+    &self.syntax_graph
+  }
+  pub fn syntax_regions(&self) -> &[SyntaxRegion<C, E, O>] {
+    &self.syntax_regions
+  }
+  pub fn syntax_graph_at(&self, index: usize) -> &SyntaxGraph<C, E, O> {
+    self
+      .syntax_regions
+      .iter()
+      .rev()
+      .find(|region| region.start <= index)
+      .map(|region| &region.syntax_graph)
+      .unwrap_or(&self.syntax_graph)
+  }
+  fn region_graphs(&self) -> Vec<(usize, &SyntaxGraph<C, E, O>)> {
+    std::iter::once((0, &self.syntax_graph))
+      .chain(
+        self
+          .syntax_regions
+          .iter()
+          .map(|region| (region.start, &region.syntax_graph)),
+      )
+      .collect()
+  }
+  pub fn syntax_trees(&self) -> &[DocumentSyntaxTree<E, O>] {
+    &self.syntax_trees
+  }
+  pub fn grapheme_indeces(&self) -> &[usize] {
+    &self.grapheme_indeces
+  }
+  pub fn newline_indeces(&self) -> &[usize] {
+    &self.newline_indeces
+  }
+  pub fn switch_syntax_graph(
+    &mut self,
+    boundary: usize,
+    syntax_graph: SyntaxGraph<C, E, O>,
+  ) -> Result<(), ParseError> {
+    let boundary = boundary.min(self.syntax_trees.len());
+    let start = self.boundary_index(boundary);
+    if start == 0 {
+      self.syntax_regions.retain(|region| region.start > 0);
+      self.syntax_graph = syntax_graph;
+    } else {
+      let region_index = self
+        .syntax_regions
+        .partition_point(|region| region.start < start);
+      let region = SyntaxRegion {
+        start,
+        syntax_graph,
+      };
+      match self.syntax_regions.get_mut(region_index) {
+        Some(existing) if existing.start == start => *existing = region,
+        _ => self.syntax_regions.insert(region_index, region),
+      }
+    }
+    self.syntax_trees.truncate(boundary);
+    self.resume_indeces.truncate(boundary);
+    self.bump_version();
+    self.parse_tail(start)
+  }
+  pub fn extend_syntax_graph(
+    &mut self,
+    boundary: usize,
+    extend: impl FnOnce(&mut SyntaxGraph<C, E, O>),
+  ) -> Result<(), ParseError> {
+    let start = self.boundary_index(boundary.min(self.syntax_trees.len()));
+    let mut syntax_graph = self.syntax_graph_at(start).clone();
+    extend(&mut syntax_graph);
+    self.switch_syntax_graph(boundary, syntax_graph)
+  }
+  fn update_indeces(&mut self, edit: &TextEdit) -> usize {
+    let delta = edit.text.len() as isize - edit.range.len() as isize;
+    let shift = |i: usize| (i as isize + delta) as usize;
+    let first_changed_grapheme = self
+      .grapheme_indeces
+      .partition_point(|&i| i <= edit.range.start)
+      .saturating_sub(2);
+    let resegment_start = self.grapheme_indeces[first_changed_grapheme];
+    let edit_end = edit.range.start + edit.text.len();
+    let mut first_unchanged_grapheme = self.grapheme_indeces.len() - 1;
+    let mut resegment_end = self.text.len();
+    for (i, _) in self.text[resegment_start..].grapheme_indices(true) {
+      let index = resegment_start + i;
+      if index < edit_end {
+        continue;
+      }
+      if let Ok(old_grapheme) = self
+        .grapheme_indeces
+        .binary_search(&((index as isize - delta) as usize))
+      {
+        first_unchanged_grapheme = old_grapheme;
+        resegment_end = index;
+        break;
+      }
+    }
+    let old_resegment_end = self.grapheme_indeces[first_unchanged_grapheme];
+    let (new_grapheme_indeces, new_newline_indeces) =
+      grapheme_and_newline_indeces(
+        &self.text[resegment_start..resegment_end],
+        resegment_start,
+      );
+    let first_changed_newline = self
+      .newline_indeces
+      .partition_point(|&i| i < resegment_start);
+    let first_unchanged_newline = self
+      .newline_indeces
+      .partition_point(|&i| i < old_resegment_end);
+    let grapheme_tail_start =
+      first_changed_grapheme + new_grapheme_indeces.len();
+    let newline_tail_start = first_changed_newline + new_newline_indeces.len();
+    self.grapheme_indeces.splice(
+      first_changed_grapheme..first_unchanged_grapheme,
+      new_grapheme_indeces,
+    );
+    self.newline_indeces.splice(
+      first_changed_newline..first_unchanged_newline,
+      new_newline_indeces,
+    );
+    for i in self.grapheme_indeces[grapheme_tail_start..]
+      .iter_mut()
+      .chain(self.newline_indeces[newline_tail_start..].iter_mut())
+    {
+      *i = shift(*i);
+    }
+    resegment_start
+  }
+  fn boundary_index(&self, boundary: usize) -> usize {
+    boundary
+      .checked_sub(1)
+      .map(|i| self.resume_indeces[i])
+      .unwrap_or(0)
+  }
+  fn update_region_starts(&mut self, edit: &TextEdit) {
+    let delta = edit.text.len() as isize - edit.range.len() as isize;
+    for region in &mut self.syntax_regions {
+      if region.start >= edit.range.end {
+        region.start = (region.start as isize + delta) as usize;
+      } else if region.start > edit.range.start {
+        region.start = edit.range.start;
+      }
+    }
+    self.syntax_regions.dedup_by(|later, earlier| {
+      if later.start == earlier.start {
+        std::mem::swap(later, earlier);
+        true
+      } else {
+        false
+      }
+    });
+    if self
+      .syntax_regions
+      .first()
+      .is_some_and(|region| region.start == 0)
+    {
+      self.syntax_graph = self.syntax_regions.remove(0).syntax_graph;
+    }
+  }
+  fn parse_tail(&mut self, index: usize) -> Result<(), ParseError> {
+    let mut resume_index = index;
+    let first_region = self
+      .syntax_regions
+      .partition_point(|region| region.start <= index);
+    for region_index in first_region..=self.syntax_regions.len() {
+      let (syntax_graph, region_start) = match region_index.checked_sub(1) {
+        Some(i) => {
+          let region = &self.syntax_regions[i];
+          (&region.syntax_graph, region.start)
+        }
+        None => (&self.syntax_graph, 0),
+      };
+      let region_end = self
+        .syntax_regions
+        .get(region_index)
+        .map(|region| region.start);
+      let mut reader = TopLevelReader::new(syntax_graph, 0);
+      reader.resume_at(resume_index.max(region_start));
+      read_syntax_trees(
+        &mut reader,
+        syntax_graph,
+        &self.text,
+        region_end,
+        &mut self.syntax_trees,
+        &mut self.resume_indeces,
+      )?;
+      if let Some(&last_resume_index) = self.resume_indeces.last() {
+        resume_index = resume_index.max(last_resume_index);
+      }
+    }
+    Ok(())
+  }
+  fn reparse_after(&mut self, index: usize) -> Result<(), ParseError> {
+    let lookahead =
+      TopLevelReader::new(self.syntax_graph_at(index), 0).top_level_lookahead();
+    let kept_trees = self
+      .resume_indeces
+      .partition_point(|&resume_index| resume_index < index)
+      .saturating_sub(lookahead);
+    self.syntax_trees.truncate(kept_trees);
+    self.resume_indeces.truncate(kept_trees);
+    self.parse_tail(self.boundary_index(kept_trees))
+  }
+  fn check_edit_ranges(
+    &self,
+    edits: &[TextEdit],
+  ) -> Result<(), InvalidDocumentIndex> {
+    let in_bounds = edits.iter().all(|edit| {
+      edit.range.start <= edit.range.end
+        && self.text.is_char_boundary(edit.range.start)
+        && self.text.is_char_boundary(edit.range.end)
+    });
+    let disjoint = edits
+      .windows(2)
+      .all(|pair| pair[1].range.end <= pair[0].range.start);
+    if in_bounds && disjoint {
+      Ok(())
+    } else {
+      Err(InvalidDocumentIndex)
+    }
+  }
+  /// The text is edited even when reparsing fails, in which case
+  /// `syntax_trees` only holds the trees that precede the parse error.
+  pub fn apply_edit(&mut self, edit: &TextEdit) -> Result<(), EditError> {
+    self.apply_edits(std::slice::from_ref(edit))
+  }
+  /// Edits must not overlap, and are applied like `apply_edit`.
+  pub fn apply_edits(&mut self, edits: &[TextEdit]) -> Result<(), EditError> {
+    let mut edits = edits.to_vec();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.range.start));
+    self.check_edit_ranges(&edits)?;
+    if edits.is_empty() {
+      return Ok(());
+    }
+    self.bump_version();
+    let mut reparse_start = 0;
+    for edit in &edits {
+      self
+        .text
+        .to_mut()
+        .replace_range(edit.range.clone(), &edit.text);
+      reparse_start = self.update_indeces(edit);
+      self.update_region_starts(edit);
+    }
+    Ok(self.reparse_after(reparse_start)?)
+  }
   pub fn get_subtree(
     &self,
     path: &[usize],
@@ -87,7 +486,7 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
   pub fn get_subtree_text(
     &self,
     path: &[usize],
-  ) -> Result<&'t str, InvalidTreePath> {
+  ) -> Result<&str, InvalidTreePath> {
     let range = self.get_subtree(path)?.range();
     Ok(
       &self.text
@@ -156,8 +555,8 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
           .iter()
           .enumerate()
           .rev()
-          .filter_map(|(i, tree)| (tree.range().end < selection.end).then(|| i))
-          .next()
+          .find(|(_, tree)| tree.range().end < selection.end)
+          .map(|(i, _)| i)
         {
           path.push(preceding_tree_index);
         }
@@ -178,7 +577,7 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
         }
       } else {
         if let Sexp::Inner(_, children) = enclosing_subtree {
-          push_preceeding_tree(&mut enclosing_path, &children);
+          push_preceeding_tree(&mut enclosing_path, children);
         }
       }
     }
@@ -188,6 +587,121 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
       self.get_subtree(&enclosing_path).unwrap().range().start
     }
   }
+  fn scanned_text(&self) -> &ScannedText<C, E, O> {
+    self.scanned_text.get_or_init(|| {
+      let tail_start = self.boundary_index(self.syntax_trees.len());
+      let regions = self.region_graphs();
+      let mut scan = MarkerScan::new(&self.syntax_graph, &self.text);
+      let (mut position, mut first_tree) = (0, 0);
+      for (region_index, &(start, syntax_graph)) in regions.iter().enumerate() {
+        let region_end = regions
+          .get(region_index + 1)
+          .map(|&(next_start, _)| next_start)
+          .unwrap_or(self.text.len());
+        let trees = &self.syntax_trees[first_tree..];
+        let trees = &trees
+          [..trees.partition_point(|tree| tree.range().start < region_end)];
+        first_tree += trees.len();
+        let start = position.max(start);
+        let end = trees
+          .last()
+          .map_or(region_end, |tree| tree.range().end)
+          .max(region_end)
+          .max(start);
+        let parsed_end = tail_start.clamp(start, end);
+        scan.switch_syntax_graph(syntax_graph);
+        scan.push_trees(trees, start..parsed_end);
+        scan.scan(parsed_end..end);
+        position = end;
+      }
+      scan.finish()
+    })
+  }
+  pub fn markers(&self) -> &[Marker<E, O>] {
+    &self.scanned_text().markers
+  }
+  pub fn tokens(&self) -> &[Token<C, E, O>] {
+    &self.scanned_text().tokens
+  }
+  pub fn marker_at(&self, index: usize) -> Option<Marker<E, O>> {
+    let markers = self.markers();
+    markers
+      .get(markers.partition_point(|marker| marker.range.end <= index))
+      .filter(|marker| marker.range.contains(&index))
+      .cloned()
+  }
+  pub fn matching_marker(&self, index: usize) -> Option<Range<usize>> {
+    self.marker_at(index).and_then(|marker| marker.partner)
+  }
+  pub fn folding_ranges(&self) -> Vec<FoldingRange<E>> {
+    let mut folding_ranges = vec![];
+    let mut stack: Vec<&DocumentSyntaxTree<E, O>> =
+      self.syntax_trees.iter().rev().collect();
+    while let Some(tree) = stack.pop() {
+      if let Sexp::Inner((range, encloser_or_operator), children) = tree {
+        if let EncloserOrOperator::Encloser(encloser) = encloser_or_operator {
+          let (start_row, _) = self.index_to_row_and_col(range.start).unwrap();
+          let (end_row, _) = self
+            .index_to_row_and_col(range.end.saturating_sub(1))
+            .unwrap();
+          if end_row > start_row {
+            folding_ranges.push(FoldingRange {
+              range: range.clone(),
+              start_row,
+              end_row,
+              encloser: encloser.clone(),
+            });
+          }
+        }
+        stack.extend(children.iter().rev());
+      }
+    }
+    folding_ranges
+  }
+  pub fn outline<K: Clone + Debug>(
+    &self,
+    outline: &Outline<E, K>,
+  ) -> Vec<OutlineSymbol<K>> {
+    outline.extract(&self.syntax_trees)
+  }
+  pub fn query(&self, pattern: &Pattern<E, O>) -> Vec<DocumentMatch<'_, E, O>> {
+    pattern.find_all(&self.syntax_trees)
+  }
+  pub fn rewrite_matches(&self, rewrite: &Rewrite<E, O>) -> Vec<RewriteMatch> {
+    rewrite.matches(self)
+  }
+  pub fn rewrite(
+    &mut self,
+    rewrite: &Rewrite<E, O>,
+  ) -> Result<usize, EditError> {
+    let edits = rewrite.edits(self);
+    self.apply_edits(&edits)?;
+    Ok(edits.len())
+  }
+  pub fn diff(&self, other: &Document<'_, C, E, O>) -> Vec<DocumentChange> {
+    diff_documents(self, other)
+  }
+  pub fn indent_for_line(
+    &self,
+    row: usize,
+    rules: &IndentRules<E>,
+  ) -> Result<Option<usize>, InvalidDocumentCharPos> {
+    rules.indent_for_line(self, row)
+  }
+  pub fn reindent_rows(
+    &self,
+    rows: Range<usize>,
+    rules: &IndentRules<E>,
+  ) -> Result<Vec<TextEdit>, InvalidDocumentCharPos> {
+    rules.reindent_rows(self, rows)
+  }
+  pub fn parinfer(
+    &self,
+    mode: ParinferMode,
+    cursor: Option<usize>,
+  ) -> Result<ParinferResult, ParinferError> {
+    parinfer_in_regions(self.region_graphs(), &self.text, mode, cursor)
+  }
   pub fn row_and_col_to_index(
     &self,
     row: usize,
@@ -1,6 +1,9 @@
 use std::collections::HashMap;
 
-use crate::{Encloser, Operator, SyntaxContext, SyntaxGraph};
+use crate::{
+  builder::{GraphMember, GraphMembers},
+  Encloser, Operator, SyntaxContext, SyntaxGraph,
+};
 
 pub fn standard_sexp_whitespace_chars() -> Vec<String> {
   vec![
@@ -41,6 +44,11 @@ impl Operator for NoOperator {
     unreachable!()
   }
 }
+impl<C> GraphMembers<C> for NoOperator {
+  fn graph_members() -> Vec<GraphMember<Self, C>> {
+    vec![]
+  }
+}
 
 pub type SexpGraph = SyntaxGraph<(), SexpEncloser, NoOperator>;
 
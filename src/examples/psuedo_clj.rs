@@ -1,202 +1,353 @@
-use crate::{Encloser, Operator, SyntaxContext, SyntaxGraph};
+use crate::{
+  format::{FormatLayout, FormatOverride, FormatRules},
+  indent::{IndentRules, IndentStyle},
+  outline::Outline,
+  reader_macro::{ReaderMacro, ReaderMacroReader, ReaderMacroResult},
+  syntax::EncloserOrOperator,
+  Encloser, Operator, Sexp, SyntaxGraph,
+};
 use std::{fmt::Debug, hash::Hash};
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, SyntaxGraph)]
+#[syntax_graph(crate = "crate", encloser = CljEncloser, operator = CljOperator)]
 pub enum CljContext {
+  #[context(root, whitespace = [" ", "\n", "\t", "\r"])]
   Default,
+  #[context(kind = Text, escape = "\\")]
   String,
+  #[context(kind = Comment)]
   Comment,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Encloser)]
+#[encloser(crate = "crate", context = CljContext, within = [Default])]
 pub enum CljEncloser {
-  List,         // (...)
-  Vector,       // [...]
-  HashMap,      // {...}
-  HashSet,      // #{...}
-  FnLiteral,    // #(...)
-  String,       // "..."
-  Regex,        // #"..."
-  LineComment,  // ;...\n
-  BlockComment, // ;...\n
-}
-impl Encloser for CljEncloser {
-  fn id_str(&self) -> &str {
-    match self {
-      CljEncloser::List => "",
-      CljEncloser::Vector => "_VECTOR_",
-      CljEncloser::HashMap => "_HASHMAP_",
-      CljEncloser::HashSet => "_HASHSET_",
-      CljEncloser::FnLiteral => "_FN_LITERAL_",
-      CljEncloser::String => "_STRING_",
-      CljEncloser::Regex => "_REGEX_",
-      CljEncloser::LineComment => "_LINE_COMMENT_",
-      CljEncloser::BlockComment => "_BLOCK_COMMENT_",
-    }
-  }
-  fn opening_encloser_str(&self) -> &str {
-    match self {
-      CljEncloser::List => "(",
-      CljEncloser::Vector => "[",
-      CljEncloser::HashMap => "{",
-      CljEncloser::HashSet => "#{",
-      CljEncloser::FnLiteral => "#(",
-      CljEncloser::String => "\"",
-      CljEncloser::Regex => "#\"",
-      CljEncloser::LineComment => ";",
-      CljEncloser::BlockComment => "/*",
-    }
-  }
-  fn closing_encloser_str(&self) -> &str {
-    match self {
-      CljEncloser::List => ")",
-      CljEncloser::Vector => "]",
-      CljEncloser::HashMap => "}",
-      CljEncloser::HashSet => "}",
-      CljEncloser::FnLiteral => ")",
-      CljEncloser::String => "\"",
-      CljEncloser::Regex => "\"",
-      CljEncloser::LineComment => "\n",
-      CljEncloser::BlockComment => {
-        "*/
-"
-      }
-    }
-  }
+  #[encloser(id = "", open = "(", close = ")", context = Default)]
+  List,
+  #[encloser(id = "_VECTOR_", open = "[", close = "]", context = Default)]
+  Vector,
+  #[encloser(id = "_HASHMAP_", open = "{", close = "}", context = Default)]
+  HashMap,
+  #[encloser(id = "_HASHSET_", open = "#{", close = "}", context = Default)]
+  HashSet,
+  #[encloser(id = "_FN_LITERAL_", open = "#(", close = ")", context = Default)]
+  FnLiteral,
+  #[encloser(id = "_STRING_", open = "\"", close = "\"", context = String)]
+  String,
+  #[encloser(id = "_REGEX_", open = "#\"", close = "\"", context = String)]
+  Regex,
+  #[encloser(
+    id = "_LINE_COMMENT_",
+    open = ";",
+    close = "\n",
+    context = Comment
+  )]
+  LineComment,
+  #[encloser(
+    id = "_BLOCK_COMMENT_",
+    open = "/*",
+    close = "*/\n",
+    context = Comment
+  )]
+  BlockComment,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Operator)]
+#[operator(crate = "crate", context = CljContext, within = [Default])]
 pub enum CljOperator {
-  Metadata,      // ^... ...
-  Quote,         // '...
-  SyntaxQuote,   // `...
-  Unquote,       // ~...
-  UnquoteSplice, // ~@...
-  Deref,         // @...
-  FormComment,   // #_...
+  #[operator(id = "_METADATA_", op = "^", right = 2, context = Default)]
+  Metadata,
+  #[operator(id = "_QUOTE_", op = "'", right = 1, context = Default)]
+  Quote,
+  #[operator(id = "_SYNTAX_QUOTE_", op = "`", right = 1, context = Default)]
+  SyntaxQuote,
+  #[operator(id = "_UNQUOTE_", op = "~", right = 1, context = Default)]
+  Unquote,
+  #[operator(id = "_UNQUOTE_SPLICE_", op = "~@", right = 1, context = Default)]
+  UnquoteSplice,
+  #[operator(id = "_DEREF_", op = "@", right = 1, context = Default)]
+  Deref,
+  #[operator(id = "_FORM_COMMENT_", op = "#_", right = 1, context = Default)]
+  FormComment,
+  #[operator(
+    id = "_TAGGED_LITERAL_",
+    op = "#",
+    right = 2,
+    context = Default,
+    within = []
+  )]
+  TaggedLiteral,
 }
-impl Operator for CljOperator {
-  fn id_str(&self) -> &str {
-    match self {
-      CljOperator::Metadata => "_METADATA_",
-      CljOperator::Quote => "_QUOTE_",
-      CljOperator::SyntaxQuote => "_SYNTAX_QUOTE_",
-      CljOperator::Unquote => "_UNQUOTE_",
-      CljOperator::UnquoteSplice => "_UNQUOTE_SPLICE_",
-      CljOperator::Deref => "_DEREF_",
-      CljOperator::FormComment => "_FORM_COMMENT_",
-    }
-  }
-  fn left_args(&self) -> usize {
-    0
+
+pub type CljSyntaxGraph = SyntaxGraph<CljContext, CljEncloser, CljOperator>;
+
+pub fn clj_graph() -> CljSyntaxGraph {
+  CljContext::syntax_graph().unwrap()
+}
+
+pub fn edn_graph() -> CljSyntaxGraph {
+  clj_graph()
+    .to_builder()
+    .update_context(CljContext::Default, |context| {
+      let context = [CljEncloser::FnLiteral, CljEncloser::Regex]
+        .iter()
+        .fold(context, |context, encloser| {
+          context.without_encloser(encloser)
+        });
+      [
+        CljOperator::Metadata,
+        CljOperator::Quote,
+        CljOperator::SyntaxQuote,
+        CljOperator::Unquote,
+        CljOperator::UnquoteSplice,
+        CljOperator::Deref,
+      ]
+      .iter()
+      .fold(context, |context, operator| {
+        context.without_operator(operator)
+      })
+    })
+    .build()
+    .unwrap()
+}
+
+type CljReader<'r> = ReaderMacroReader<'r, CljEncloser, CljOperator>;
+type CljReaderResult = ReaderMacroResult<CljEncloser, CljOperator>;
+
+fn read_char_literal(reader: &mut CljReader) -> CljReaderResult {
+  let start = reader.marker_range().start;
+  if reader.advance(1).is_empty() {
+    return Err(reader.error("missing character"));
   }
+  reader.read_while(|grapheme| grapheme.chars().all(char::is_alphanumeric));
+  Ok(vec![reader.leaf(start..reader.position())])
+}
 
-  fn right_args(&self) -> usize {
-    match self {
-      CljOperator::Metadata => 2,
-      CljOperator::Quote => 1,
-      CljOperator::SyntaxQuote => 1,
-      CljOperator::Unquote => 1,
-      CljOperator::UnquoteSplice => 1,
-      CljOperator::Deref => 1,
-      CljOperator::FormComment => 1,
-    }
+fn read_tagged_literal(reader: &mut CljReader) -> CljReaderResult {
+  let marker = reader.marker_range();
+  let tag = reader
+    .leaf(marker.start + CljOperator::TaggedLiteral.op_str().len()..marker.end);
+  reader.read_while(|grapheme| grapheme.trim().is_empty());
+  match reader.read_form()? {
+    Some(form) => Ok(vec![Sexp::Inner(
+      (
+        marker.start..form.range().end,
+        EncloserOrOperator::Operator(CljOperator::TaggedLiteral),
+      ),
+      vec![tag, form],
+    )]),
+    None => Err(reader.error("missing tagged form")),
   }
+}
 
-  fn op_str(&self) -> &str {
-    match self {
-      CljOperator::Metadata => "^",
-      CljOperator::Quote => "'",
-      CljOperator::SyntaxQuote => "`",
-      CljOperator::Unquote => "~",
-      CljOperator::UnquoteSplice => "~@",
-      CljOperator::Deref => "@",
-      CljOperator::FormComment => "#_",
-    }
+fn read_conditional(reader: &mut CljReader) -> CljReaderResult {
+  let Some(Sexp::Inner(
+    (_, EncloserOrOperator::Encloser(CljEncloser::List)),
+    forms,
+  )) = reader.read_form()?
+  else {
+    return Err(reader.error("expected a list of features and forms"));
+  };
+  Ok(
+    forms
+      .chunks(2)
+      .find(|pair| matches!(&pair[0], Sexp::Leaf(_, name) if name == ":clj"))
+      .and_then(|pair| pair.get(1).cloned())
+      .into_iter()
+      .collect(),
+  )
+}
+
+pub fn clj_graph_with_reader_macros() -> CljSyntaxGraph {
+  let mut graph = clj_graph()
+    .to_builder()
+    .update_context(CljContext::Default, |context| {
+      context.with_operator(CljOperator::TaggedLiteral, CljContext::Default)
+    })
+    .build()
+    .unwrap();
+  for reader_macro in [
+    ReaderMacro::new("\\", read_char_literal),
+    ReaderMacro::new("#inst", read_tagged_literal),
+    ReaderMacro::new("#uuid", read_tagged_literal),
+    ReaderMacro::new("#?", read_conditional),
+  ] {
+    graph
+      .add_reader_macro(&CljContext::Default, reader_macro)
+      .unwrap();
   }
+  graph
 }
 
-pub type CljSyntaxGraph = SyntaxGraph<CljContext, CljEncloser, CljOperator>;
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CljSymbolKind {
+  Namespace,
+  Var,
+  Function,
+  Macro,
+  Protocol,
+  Record,
+}
 
-pub fn clj_graph() -> CljSyntaxGraph {
-  CljSyntaxGraph::new(
-    CljContext::Default,
-    [
-      (
-        CljContext::Default,
-        SyntaxContext::new(
-          vec![
-            CljEncloser::List,
-            CljEncloser::Vector,
-            CljEncloser::HashMap,
-            CljEncloser::HashSet,
-            CljEncloser::FnLiteral,
-            CljEncloser::String,
-            CljEncloser::Regex,
-            CljEncloser::LineComment,
-            CljEncloser::BlockComment,
-          ],
-          vec![
-            CljOperator::Metadata,
-            CljOperator::Quote,
-            CljOperator::SyntaxQuote,
-            CljOperator::Unquote,
-            CljOperator::UnquoteSplice,
-            CljOperator::Deref,
-            CljOperator::FormComment,
-          ],
-          None,
-          vec![
-            " ".to_string(),
-            "\n".to_string(),
-            "\t".to_string(),
-            "\r".to_string(),
-          ],
-        ),
-      ),
-      (
-        CljContext::String,
-        SyntaxContext::new(vec![], vec![], Some('\\'.to_string()), vec![]),
-      ),
-      (
-        CljContext::Comment,
-        SyntaxContext::new(vec![], vec![], None, vec![]),
+pub fn clj_outline() -> Outline<CljEncloser, CljSymbolKind> {
+  [
+    ("ns", CljSymbolKind::Namespace),
+    ("def", CljSymbolKind::Var),
+    ("defonce", CljSymbolKind::Var),
+    ("defn", CljSymbolKind::Function),
+    ("defn-", CljSymbolKind::Function),
+    ("defmulti", CljSymbolKind::Function),
+    ("defmacro", CljSymbolKind::Macro),
+    ("defprotocol", CljSymbolKind::Protocol),
+    ("defrecord", CljSymbolKind::Record),
+  ]
+  .into_iter()
+  .fold(Outline::new(), |outline, (head, kind)| {
+    outline.with_rule(CljEncloser::List, head, kind)
+  })
+}
+
+pub fn clj_indent_rules() -> IndentRules<CljEncloser> {
+  [
+    "ns",
+    "def",
+    "defn",
+    "defn-",
+    "defmacro",
+    "defmulti",
+    "defmethod",
+    "defprotocol",
+    "defrecord",
+    "deftype",
+    "fn",
+    "let",
+    "letfn",
+    "binding",
+    "loop",
+    "do",
+    "when",
+    "when-not",
+    "when-let",
+    "if-let",
+    "doseq",
+    "dotimes",
+    "for",
+    "case",
+    "cond",
+    "condp",
+    "try",
+    "catch",
+    "finally",
+    "comment",
+  ]
+  .into_iter()
+  .fold(
+    IndentRules::new(IndentStyle::AlignWithFirstArgument)
+      .with_encloser_style(
+        CljEncloser::Vector,
+        IndentStyle::AlignWithFirstElement,
+      )
+      .with_encloser_style(
+        CljEncloser::HashMap,
+        IndentStyle::AlignWithFirstElement,
+      )
+      .with_encloser_style(
+        CljEncloser::HashSet,
+        IndentStyle::AlignWithFirstElement,
       ),
-    ]
-    .into(),
-    [
-      (CljEncloser::List, CljContext::Default),
-      (CljEncloser::Vector, CljContext::Default),
-      (CljEncloser::HashMap, CljContext::Default),
-      (CljEncloser::HashSet, CljContext::Default),
-      (CljEncloser::FnLiteral, CljContext::Default),
-      (CljEncloser::LineComment, CljContext::Comment),
-      (CljEncloser::BlockComment, CljContext::Comment),
-      (CljEncloser::Regex, CljContext::String),
-      (CljEncloser::String, CljContext::String),
-    ]
-    .into_iter()
-    .collect(),
-    [
-      (CljOperator::Metadata, CljContext::Default),
-      (CljOperator::Quote, CljContext::Default),
-      (CljOperator::SyntaxQuote, CljContext::Default),
-      (CljOperator::Unquote, CljContext::Default),
-      (CljOperator::UnquoteSplice, CljContext::Default),
-      (CljOperator::Deref, CljContext::Default),
-      (CljOperator::FormComment, CljContext::Default),
-    ]
-    .into_iter()
-    .collect(),
+    |rules, head| rules.with_body_head(head, 2),
+  )
+}
+
+pub fn clj_format_rules() -> FormatRules<CljEncloser, CljOperator> {
+  let block = |args| FormatLayout::Block { args, indent: 2 };
+  let binding_block = FormatOverride::new(block(1))
+    .with_child(1, FormatOverride::new(FormatLayout::Pairs { align: false }));
+  let rules = [
+    "let",
+    "loop",
+    "binding",
+    "with-open",
+    "with-redefs",
+    "when-let",
+    "when-some",
+    "if-let",
+    "if-some",
+    "doseq",
+    "dotimes",
+    "for",
+  ]
+  .into_iter()
+  .fold(FormatRules::new(), |rules, head| {
+    rules.with_head_rule(
+      EncloserOrOperator::Encloser(CljEncloser::List),
+      head,
+      binding_block.clone(),
+    )
+  });
+  [
+    ("do", 0),
+    ("try", 0),
+    ("finally", 0),
+    ("comment", 0),
+    ("cond", 0),
+    ("ns", 1),
+    ("def", 1),
+    ("defonce", 1),
+    ("defmulti", 1),
+    ("defprotocol", 1),
+    ("fn", 1),
+    ("letfn", 1),
+    ("when", 1),
+    ("when-not", 1),
+    ("if", 1),
+    ("if-not", 1),
+    ("case", 1),
+    ("defn", 2),
+    ("defn-", 2),
+    ("defmacro", 2),
+    ("defmethod", 2),
+    ("defrecord", 2),
+    ("deftype", 2),
+    ("catch", 2),
+  ]
+  .into_iter()
+  .fold(rules, |rules, (head, args)| {
+    rules.with_head_rule(
+      EncloserOrOperator::Encloser(CljEncloser::List),
+      head,
+      FormatOverride::new(block(args)),
+    )
+  })
+  .with_form_rule(
+    EncloserOrOperator::Encloser(CljEncloser::HashMap),
+    FormatOverride::new(FormatLayout::Pairs { align: true }),
   )
 }
 
 #[cfg(test)]
 mod pseudo_clj_tests {
   use crate::{
-    examples::psuedo_clj::{clj_graph, CljEncloser, CljOperator},
-    syntax::EncloserOrOperator,
-    Parser, SyntaxTree,
+    analysis::{Ambiguity, AmbiguityKind},
+    desugar::{InnerTag, PrefixTag, WrappedTag},
+    diff::{diff, DocumentChange, TreeChange},
+    document::{Document, TextEdit},
+    examples::psuedo_clj::{
+      clj_format_rules, clj_graph, clj_graph_with_reader_macros,
+      clj_indent_rules, clj_outline, edn_graph, CljContext, CljEncloser,
+      CljOperator, CljSymbolKind, CljSyntaxGraph,
+    },
+    format::{FormatLayout, FormatOverride, FormatRules, Formatter},
+    grammar::{read_grammar, write_grammar},
+    markers::{MarkerKind, Token, TokenKind},
+    merge::{merge_documents, MergeConflict, MergeResult},
+    outline::OutlineSymbol,
+    parinfer::{parinfer, ParinferError, ParinferMode},
+    pretty::PrettyPrinter,
+    query::{Pattern, PatternError},
+    rewrite::{Rewrite, RewriteError, RewriteMatch},
+    syntax::{ContextKind, EncloserOrOperator},
+    ParseError, Parser, RawSexp, ResugarError, Sexp, SyntaxTree,
   };
   use CljEncloser::*;
   use CljOperator::*;
@@ -268,4 +419,1031 @@ mod pseudo_clj_tests {
       )))
     )
   }
+
+  #[test]
+  fn tokens() {
+    let doc =
+      Document::from_text_with_syntax(clj_graph(), "(str \"a b\" ; hi\n x)")
+        .unwrap();
+    fn token(
+      range: std::ops::Range<usize>,
+      kind: TokenKind<CljContext, CljEncloser, CljOperator>,
+    ) -> Token<CljContext, CljEncloser, CljOperator> {
+      Token { range, kind }
+    }
+    use TokenKind::*;
+    assert_eq!(
+      doc.tokens(),
+      vec![
+        token(0..1, Marker(MarkerKind::Opener(List))),
+        token(1..4, Leaf(CljContext::Default)),
+        token(4..5, Whitespace),
+        token(5..6, Marker(MarkerKind::Opener(String))),
+        token(6..9, Leaf(CljContext::String)),
+        token(9..10, Marker(MarkerKind::Closer(String))),
+        token(10..11, Whitespace),
+        token(11..12, Marker(MarkerKind::Opener(LineComment))),
+        token(12..15, Leaf(CljContext::Comment)),
+        token(15..16, Marker(MarkerKind::Closer(LineComment))),
+        token(16..17, Whitespace),
+        token(17..18, Leaf(CljContext::Default)),
+        token(18..19, Marker(MarkerKind::Closer(List))),
+      ]
+    );
+  }
+
+  #[test]
+  fn outline() {
+    let doc = Document::from_text_with_syntax(
+      clj_graph(),
+      "(ns my.app)
+(def ^:private x 1)
+(comment
+  (defn f [a]
+    (defmacro m [] a)))",
+    )
+    .unwrap();
+    assert_eq!(
+      doc.outline(&clj_outline()),
+      vec![
+        OutlineSymbol {
+          kind: CljSymbolKind::Namespace,
+          name: "my.app".to_string(),
+          range: 0..11,
+          name_range: 4..10,
+          children: vec![]
+        },
+        OutlineSymbol {
+          kind: CljSymbolKind::Var,
+          name: "x".to_string(),
+          range: 12..31,
+          name_range: 27..28,
+          children: vec![]
+        },
+        OutlineSymbol {
+          kind: CljSymbolKind::Function,
+          name: "f".to_string(),
+          range: 43..77,
+          name_range: 49..50,
+          children: vec![OutlineSymbol {
+            kind: CljSymbolKind::Macro,
+            name: "m".to_string(),
+            range: 59..76,
+            name_range: 69..70,
+            children: vec![]
+          }]
+        }
+      ]
+    );
+  }
+
+  #[test]
+  fn indent_for_line() {
+    let doc = Document::from_text_with_syntax(
+      clj_graph(),
+      "(defn f [a
+b]
+(let [x 1]
+(+ x
+a
+#{1
+2})
+\"multi
+line\"))
+(foo
+bar)",
+    )
+    .unwrap();
+    let rules = clj_indent_rules();
+    let indents: Vec<_> = (0..10)
+      .map(|row| doc.indent_for_line(row, &rules).unwrap())
+      .collect();
+    assert_eq!(
+      indents,
+      vec![
+        Some(0),
+        Some(9),
+        Some(2),
+        Some(2),
+        Some(3),
+        Some(3),
+        Some(2),
+        Some(2),
+        None,
+        Some(0),
+      ]
+    );
+    assert_eq!(doc.indent_for_line(10, &rules).unwrap(), Some(1));
+  }
+
+  #[test]
+  fn reindent_rows() {
+    let mut doc = Document::from_string_with_syntax(
+      clj_graph(),
+      "(let [x 1]
+        (+ x
+       {:a 1
+  :b 2}))"
+        .to_string(),
+    )
+    .unwrap();
+    let edits = doc.reindent_rows(0..4, &clj_indent_rules()).unwrap();
+    assert_eq!(
+      edits,
+      vec![
+        TextEdit::new(11..19, "  "),
+        TextEdit::new(24..31, "     "),
+        TextEdit::new(37..39, "      "),
+      ]
+    );
+    doc.apply_edits(&edits).unwrap();
+    assert_eq!(
+      doc.text,
+      "(let [x 1]
+  (+ x
+     {:a 1
+      :b 2}))"
+    );
+  }
+
+  #[test]
+  fn parinfer_indent_mode() {
+    let infer = |text: &str| {
+      parinfer(&clj_graph(), text, ParinferMode::Indent, None)
+        .unwrap()
+        .text
+    };
+    assert_eq!(infer("(defn f [a]\n  (+ a 1"), "(defn f [a]\n  (+ a 1))");
+    assert_eq!(
+      infer("(let [x 1]\n  (foo x))\n  bar"),
+      "(let [x 1]\n  (foo x)\n  bar)"
+    );
+    assert_eq!(infer("(foo \"a)\" ; b)\n  c"), "(foo \"a)\" ; b)\n  c)");
+    assert_eq!(infer("(foo ])\nbar"), "(foo)\nbar");
+    assert_eq!(infer("(foo]"), "(foo)");
+    assert_eq!(
+      parinfer(&clj_graph(), "(foo \"bar", ParinferMode::Indent, None),
+      Err(ParinferError::UnclosedEncloser(5..6))
+    );
+  }
+
+  #[test]
+  fn parinfer_indent_mode_cursor() {
+    let result =
+      parinfer(&clj_graph(), "(foo)\n  bar", ParinferMode::Indent, Some(5))
+        .unwrap();
+    assert_eq!(result.text, "(foo)\n  bar");
+    let result =
+      parinfer(&clj_graph(), "(foo)\n  bar", ParinferMode::Indent, Some(11))
+        .unwrap();
+    assert_eq!(result.text, "(foo\n  bar)");
+    assert_eq!(result.cursor, Some(10));
+  }
+
+  #[test]
+  fn parinfer_paren_mode() {
+    let infer = |text: &str| {
+      parinfer(&clj_graph(), text, ParinferMode::Paren, None)
+        .unwrap()
+        .text
+    };
+    assert_eq!(infer("(foo\nbar)"), "(foo\n bar)");
+    assert_eq!(infer("(foo)\n    bar"), "(foo)\nbar");
+    assert_eq!(infer("(let [x 1]\n(foo\nx))"), "(let [x 1]\n (foo\n  x))");
+    assert_eq!(infer("\"a\nb\""), "\"a\nb\"");
+    assert_eq!(
+      parinfer(&clj_graph(), "(foo", ParinferMode::Paren, None),
+      Err(ParinferError::UnclosedEncloser(0..1))
+    );
+    assert_eq!(
+      parinfer(&clj_graph(), "(foo))", ParinferMode::Paren, None),
+      Err(ParinferError::UnmatchedCloser(5..6))
+    );
+  }
+
+  #[test]
+  fn parinfer_context_kinds() {
+    let infer = |graph: &CljSyntaxGraph, text: &str, mode| {
+      parinfer(graph, text, mode, None).unwrap().text
+    };
+    let graph = clj_graph_with_reader_macros();
+    assert_eq!(
+      infer(&graph, "(str \\()\n  x", ParinferMode::Indent),
+      "(str \\(\n  x)"
+    );
+    assert_eq!(
+      infer(&graph, "(a #?(:clj 1\n:cljs 2) b)\nc", ParinferMode::Indent),
+      "(a #?(:clj 1\n:cljs 2) b)\nc"
+    );
+    let graph = clj_graph()
+      .to_builder()
+      .update_context(CljContext::String, |context| {
+        context.with_whitespace(" ")
+      })
+      .build()
+      .unwrap();
+    assert_eq!(
+      graph.get_context(&CljContext::String).kind(),
+      ContextKind::Text
+    );
+    assert_eq!(
+      infer(&graph, "(foo \"a\nb\")", ParinferMode::Paren),
+      "(foo \"a\nb\")"
+    );
+    assert_eq!(
+      infer(&graph, "(foo ; a\n  b", ParinferMode::Indent),
+      "(foo ; a\n  b)"
+    );
+  }
+
+  #[test]
+  fn document_parinfer_edits() {
+    let mut doc =
+      Document::from_string_with_syntax(clj_graph(), "(a\n b)".to_string())
+        .unwrap();
+    let _ = doc.apply_edit(&TextEdit::new(3..3, "(c\n"));
+    assert_eq!(doc.text, "(a\n(c\n b)");
+    let result = doc.parinfer(ParinferMode::Indent, None).unwrap();
+    assert_eq!(result.text, "(a)\n(c\n b)");
+    doc.apply_edits(&result.edits).unwrap();
+    assert_eq!(doc.text, result.text);
+  }
+
+  fn parse_trees(text: &str) -> Vec<SyntaxTree<CljEncloser, CljOperator>> {
+    Parser::new(clj_graph(), text)
+      .read_all()
+      .into_iter()
+      .map(|tree| tree.unwrap().into())
+      .collect()
+  }
+
+  fn pretty_print(text: &str, width: usize) -> std::string::String {
+    PrettyPrinter::new(&clj_graph(), width).print_all(&parse_trees(text))
+  }
+
+  #[test]
+  fn pretty_print_fits_width() {
+    assert_eq!(
+      pretty_print("(defn f [a b]\n  (+ a b))", 80),
+      "(defn f [a b] (+ a b))"
+    );
+    assert_eq!(
+      pretty_print("(defn f [a b] (+ a b))", 16),
+      "(defn\n f\n [a b]\n (+ a b))"
+    );
+    assert_eq!(
+      pretty_print("(foo (bar baz qux) #{1 2 3})", 20),
+      "(foo\n (bar baz qux)\n #{1 2 3})"
+    );
+  }
+
+  #[test]
+  fn pretty_print_sugar() {
+    assert_eq!(
+      pretty_print("(def ^:private x '(1 2))  @y #_ z", 80),
+      "(def ^:private x '(1 2))\n@y\n#_z"
+    );
+    assert_eq!(
+      pretty_print("(str \"a  b\" #\"x y\")", 10),
+      "(str\n \"a  b\"\n #\"x y\")"
+    );
+    assert_eq!(pretty_print("(a ; c\n b)", 80), "(a\n ; c\n b)");
+  }
+
+  fn format(text: &str, width: usize) -> std::string::String {
+    Formatter::new(&clj_graph(), width, clj_format_rules())
+      .format_all(&parse_trees(text))
+  }
+
+  #[test]
+  fn format_body_indentation() {
+    assert_eq!(
+      format("(defn f [a b] (+ a b) (* a b))", 80),
+      "(defn f [a b] (+ a b) (* a b))"
+    );
+    assert_eq!(
+      format("(defn f [a b] (+ a b) (* a b))", 20),
+      "(defn f [a b]\n  (+ a b)\n  (* a b))"
+    );
+    assert_eq!(format("(do (foo) (bar))", 10), "(do\n  (foo)\n  (bar))");
+  }
+
+  #[test]
+  fn format_binding_vectors() {
+    assert_eq!(
+      format("(let [a 1 b 2] (+ a b))", 14),
+      "(let [a 1 b 2]\n  (+ a b))"
+    );
+    assert_eq!(
+      format("(let [a 1 b 2] (+ a b))", 10),
+      "(let [a 1\n      b 2]\n  (+ a b))"
+    );
+  }
+
+  #[test]
+  fn format_map_alignment() {
+    assert_eq!(format("{:a 1 :bcd 2}", 80), "{:a 1 :bcd 2}");
+    assert_eq!(format("{:a 1 :bcd 2}", 10), "{:a   1\n :bcd 2}");
+    assert_eq!(
+      format("{:a 1 :b {:c 2 :de 3}}", 16),
+      "{:a 1\n :b {:c  2\n     :de 3}}"
+    );
+  }
+
+  #[test]
+  fn format_custom_rule() {
+    let rules = FormatRules::new().with_custom_rule(|tree| match tree {
+      Sexp::Inner(Encloser(Vector), children)
+        if children.iter().all(
+          |child| matches!(child, Sexp::Leaf(_, leaf) if leaf.starts_with(':')),
+        ) =>
+      {
+        Some(FormatOverride::new(FormatLayout::Pairs { align: true }))
+      }
+      _ => None,
+    });
+    assert_eq!(
+      Formatter::new(&clj_graph(), 8, rules)
+        .format_all(&parse_trees("[:a :b :cc :d] [x y z]")),
+      "[:a  :b\n :cc :d]\n[x y z]"
+    );
+  }
+
+  #[test]
+  fn resugar_round_trip() {
+    let graph = clj_graph();
+    for tree in parse_trees(
+      "(defn f [a] ^:private x '(y @z) #{1 \"s\"} {:a #\"r\"} #(% 1) ())",
+    ) {
+      assert_eq!(graph.resugar(&RawSexp::from(tree.clone())), Ok(tree));
+    }
+  }
+
+  #[test]
+  fn resugar_errors() {
+    fn leaf(s: &str) -> RawSexp {
+      RawSexp::leaf(s.to_string())
+    }
+    let graph = clj_graph();
+    assert_eq!(
+      graph.resugar(&RawSexp::inner(vec![
+        leaf("f"),
+        RawSexp::inner(vec![leaf("_QUOTE_"), leaf("a"), leaf("b")]),
+        RawSexp::inner(
+          vec![leaf("_STRING_"), RawSexp::inner(vec![leaf("x")]),]
+        ),
+      ])),
+      Err(vec![
+        ResugarError::OperatorArity {
+          path: vec![1],
+          operator: "_QUOTE_".to_string(),
+          expected: 1,
+          found: 2,
+        },
+        ResugarError::UnrecognizedForm { path: vec![2, 1] },
+      ])
+    );
+  }
+
+  #[test]
+  fn desugar_strategies() {
+    let graph = clj_graph();
+    let tree = parse_trees("(_VECTOR_ [\\x] ())").remove(0);
+    let prefixed = graph.desugar_with(&PrefixTag, &tree);
+    assert_eq!(prefixed.to_string(), "(_VECTOR_ (_VECTOR_ \\x) ())");
+    assert_ne!(graph.resugar_with(&PrefixTag, &prefixed), Ok(tree.clone()));
+    let wrapped = graph.desugar_with(&WrappedTag::default(), &tree);
+    assert_eq!(wrapped.to_string(), "(\\ \\_VECTOR_ (_VECTOR_ \\\\x) (\\))");
+    assert_eq!(
+      graph.resugar_with(&WrappedTag::default(), &wrapped),
+      Ok(tree.clone())
+    );
+    let tagged = graph.desugar_with(&InnerTag, &tree);
+    assert_eq!(
+      tagged,
+      Sexp::Inner(
+        "".to_string(),
+        vec![
+          Sexp::Leaf((), "_VECTOR_".to_string()),
+          Sexp::Inner(
+            "_VECTOR_".to_string(),
+            vec![Sexp::Leaf((), "\\x".to_string())]
+          ),
+          Sexp::Inner("".to_string(), vec![]),
+        ]
+      )
+    );
+    assert_eq!(graph.resugar_with(&InnerTag, &tagged), Ok(tree));
+  }
+
+  #[test]
+  fn query_captures() {
+    let doc = Document::from_text_with_syntax(
+      clj_graph(),
+      "(defn f [a b] (+ a b))
+(defn g [] 1 2)
+(def h 3)",
+    )
+    .unwrap();
+    let pattern =
+      Pattern::parse(&clj_graph(), "(defn ?name [?args*] ?body+)").unwrap();
+    let matches = doc.query(&pattern);
+    assert_eq!(matches.len(), 2);
+    assert_eq!(matches[0].path, vec![0]);
+    assert_eq!(matches[0].range, Some(0..22));
+    let name = matches[0].capture("name").unwrap();
+    assert_eq!(
+      (name.path.clone(), name.range.clone()),
+      (vec![0, 1], Some(6..7))
+    );
+    assert_eq!(
+      matches[0].captures["args"]
+        .iter()
+        .map(|captured| captured.range.clone().unwrap())
+        .collect::<Vec<_>>(),
+      vec![9..10, 11..12]
+    );
+    assert_eq!(matches[0].captures["body"].len(), 1);
+    assert!(matches[1].captures["args"].is_empty());
+    assert_eq!(matches[1].captures["body"].len(), 2);
+  }
+
+  #[test]
+  fn query_kind_constraints_and_wildcards() {
+    let trees = parse_trees("(f [1] x) (f {} y) (f x [2]) (g)");
+    let find = |pattern: &str| {
+      Pattern::parse(&clj_graph(), pattern)
+        .unwrap()
+        .find_all(&trees)
+        .into_iter()
+        .map(|found| found.path)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(find("(f ?v:_VECTOR_ _)"), vec![vec![0]]);
+    assert_eq!(find("(f _ ?x:leaf)"), vec![vec![0], vec![1]]);
+    assert_eq!(find("(_ _*)"), vec![vec![0], vec![1], vec![2], vec![3]]);
+    assert_eq!(find("[_]"), vec![vec![0, 1], vec![2, 2]]);
+    assert_eq!(find("(_+ [2])"), vec![vec![2]]);
+    assert_eq!(
+      Pattern::parse(&clj_graph(), "?x*"),
+      Err(PatternError::TopLevelRepetition)
+    );
+    assert_eq!(
+      Pattern::parse(&clj_graph(), "a b"),
+      Err(PatternError::MultipleForms)
+    );
+  }
+
+  #[test]
+  fn query_repeated_captures_and_escaped_literals() {
+    let trees = parse_trees(
+      "(= a a) (= a b) (= [x 1] [x 1]) (= [x] [x 1]) (?x 1) (fn [_] 3) \
+       (fn [a] 3)",
+    );
+    let find = |pattern: &str| {
+      Pattern::parse(&clj_graph(), pattern)
+        .unwrap()
+        .find_all(&trees)
+        .into_iter()
+        .map(|found| found.path)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(find("(= ?x ?x)"), vec![vec![0], vec![2]]);
+    assert_eq!(find("(= [?xs*] [?xs*])"), vec![vec![2]]);
+    assert_eq!(find("(??x _)"), vec![vec![4]]);
+    assert_eq!(find("(?x _)").len(), 1);
+    assert_eq!(find("(fn [??_] ?body)"), vec![vec![5]]);
+    assert_eq!(find("(fn [_] ?body)"), vec![vec![5], vec![6]]);
+    let pattern = Pattern::parse(&clj_graph(), "(= ?x ?x)").unwrap();
+    let matches = pattern.find_all(&trees);
+    assert_eq!(matches[0].captures["x"].len(), 1);
+    assert_eq!(matches[0].capture("x").unwrap().path, vec![0, 1]);
+  }
+
+  #[test]
+  fn rewrite_document() {
+    let mut doc = Document::from_string_with_syntax(
+      clj_graph(),
+      "(defn f [a] ; keep\n  (inc a))\n\n(defn g [] 1)".to_string(),
+    )
+    .unwrap();
+    let rewrite = Rewrite::parse(
+      &clj_graph(),
+      "(defn ?name ?args ?body*)",
+      "(defn- ?name ?args ?body*)",
+    )
+    .unwrap();
+    assert_eq!(
+      doc.rewrite_matches(&rewrite),
+      vec![
+        RewriteMatch {
+          path: vec![0],
+          range: 0..29,
+          original: "(defn f [a] ; keep\n  (inc a))".to_string(),
+          replacement: "(defn- f [a] ; keep\n  (inc a))".to_string(),
+        },
+        RewriteMatch {
+          path: vec![1],
+          range: 31..44,
+          original: "(defn g [] 1)".to_string(),
+          replacement: "(defn- g [] 1)".to_string(),
+        },
+      ]
+    );
+    assert_eq!(
+      rewrite.edits(&doc),
+      vec![TextEdit::new(5..5, "-"), TextEdit::new(36..36, "-")]
+    );
+    assert_eq!(doc.rewrite(&rewrite), Ok(2));
+    assert_eq!(
+      doc.text,
+      "(defn- f [a] ; keep\n  (inc a))\n\n(defn- g [] 1)"
+    );
+    assert_eq!(doc.rewrite(&rewrite), Ok(0));
+  }
+
+  #[test]
+  fn rewrite_skips_nested_matches() {
+    let doc =
+      Document::from_text_with_syntax(clj_graph(), "(inc (inc 1)) [(inc x)]")
+        .unwrap();
+    let rewrite = Rewrite::parse(&clj_graph(), "(inc ?x)", "(+ ?x 1)").unwrap();
+    assert_eq!(
+      rewrite
+        .matches(&doc)
+        .into_iter()
+        .map(|rewrite_match| rewrite_match.replacement)
+        .collect::<Vec<_>>(),
+      vec!["(+ (inc 1) 1)", "(+ x 1)"]
+    );
+    assert_eq!(
+      Rewrite::parse(&clj_graph(), "(inc ?x)", "(+ ?y 1)").unwrap_err(),
+      RewriteError::UnknownCapture("y".to_string())
+    );
+    let doc = Document::from_text_with_syntax(clj_graph(), "(foo 1)").unwrap();
+    let rewrite = Rewrite::parse(
+      &clj_graph(),
+      "(foo ?x)",
+      "(bar ?x ??y \"?x is the arg\" ; ?x too\n)",
+    )
+    .unwrap();
+    assert_eq!(
+      rewrite.matches(&doc)[0].replacement,
+      "(bar 1 ?y \"?x is the arg\" ; ?x too\n)"
+    );
+  }
+
+  #[test]
+  fn diff_documents() {
+    let old = Document::from_text_with_syntax(
+      clj_graph(),
+      "(defn f [a b] (+ a b))\n(def x 1)\n(def y 2)",
+    )
+    .unwrap();
+    let new = Document::from_text_with_syntax(
+      clj_graph(),
+      "(def y 2)\n(defn f [a c] (+ a c 1))\n(def x 1)",
+    )
+    .unwrap();
+    assert_eq!(
+      old.diff(&new),
+      vec![
+        DocumentChange {
+          change: TreeChange::Moved {
+            old_path: vec![1],
+            new_path: vec![2],
+          },
+          old_range: Some(23..32),
+          new_range: Some(35..44),
+        },
+        DocumentChange {
+          change: TreeChange::Changed {
+            old_path: vec![0, 2, 1],
+            new_path: vec![1, 2, 1],
+          },
+          old_range: Some(11..12),
+          new_range: Some(21..22),
+        },
+        DocumentChange {
+          change: TreeChange::Changed {
+            old_path: vec![0, 3, 2],
+            new_path: vec![1, 3, 2],
+          },
+          old_range: Some(19..20),
+          new_range: Some(29..30),
+        },
+        DocumentChange {
+          change: TreeChange::Inserted {
+            new_path: vec![1, 3, 3],
+          },
+          old_range: None,
+          new_range: Some(31..32),
+        },
+      ]
+    );
+  }
+
+  #[test]
+  fn diff_moves_between_forms() {
+    assert_eq!(
+      diff(
+        &parse_trees("(a [x y] b) (c)"),
+        &parse_trees("(a [y] b) (c x)")
+      ),
+      vec![TreeChange::Moved {
+        old_path: vec![0, 1, 0],
+        new_path: vec![1, 1],
+      }]
+    );
+    assert_eq!(
+      diff(&parse_trees("(a b) [c]"), &parse_trees("(a) {c 1}")),
+      vec![
+        TreeChange::Deleted {
+          old_path: vec![0, 1],
+        },
+        TreeChange::Deleted { old_path: vec![1] },
+        TreeChange::Inserted { new_path: vec![1] },
+      ]
+    );
+  }
+
+  fn merge_texts(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let document =
+      |text| Document::from_text_with_syntax(clj_graph(), text).unwrap();
+    merge_documents(&document(base), &document(ours), &document(theirs))
+  }
+
+  #[test]
+  fn merge_non_overlapping_edits() {
+    let merged = merge_texts(
+      "(ns app)\n\n(defn f [x]\n  (+ x 1))\n\n(def y 2)\n",
+      "(ns app)\n\n(defn f [x]\n  (+ x 2))\n\n(def y 2)\n\n(def z 3)\n",
+      "(ns app (:require foo))\n\n(defn f [x y]\n  (+ x 1))\n\n(def y 2)\n",
+    );
+    assert!(merged.is_clean());
+    assert_eq!(
+      merged.text,
+      "(ns app (:require foo))\n\n(defn f [x y]\n  (+ x 2))\n\n\
+       (def y 2)\n\n(def z 3)\n"
+    );
+    assert_eq!(
+      merge_texts("[1 2 3]", "[0 1 2 3]", "[1 2 3 4]").text,
+      "[0 1 2 3 4]"
+    );
+    assert_eq!(merge_texts("'(a b)", "'(a c)", "'(d b)").text, "'(d c)");
+  }
+
+  #[test]
+  fn merge_conflicts() {
+    let merged = merge_texts("(def x 1)", "(def x 2)", "(def x 3)");
+    assert_eq!(
+      merged.text,
+      "(def x (merge-conflict\n(ours 2)\n(base 1)\n(theirs 3)))"
+    );
+    assert_eq!(
+      merged.conflicts,
+      vec![MergeConflict {
+        range: 7..52,
+        base: "1".to_string(),
+        ours: "2".to_string(),
+        theirs: "3".to_string(),
+      }]
+    );
+    let merged = merge_texts("(a)\n(b)", "(a)", "(a)\n(b c)");
+    assert_eq!(
+      merged.conflicts,
+      vec![MergeConflict {
+        range: 4..53,
+        base: "(b)".to_string(),
+        ours: "".to_string(),
+        theirs: "(b c)".to_string(),
+      }]
+    );
+    assert_eq!(
+      parse_trees(&merged.text)[1],
+      parse_trees("(merge-conflict (ours) (base (b)) (theirs (b c)))")[0]
+    );
+    let merged = merge_texts("[1 \"a\"]", "[1 \"b\"]", "[1 \"c\"]");
+    assert_eq!(
+      merged.text,
+      "[1 (merge-conflict\n(ours \"b\")\n(base \"a\")\n(theirs \"c\"))]"
+    );
+  }
+
+  #[test]
+  fn grammar_round_trip() {
+    let grammar = write_grammar(&clj_graph());
+    assert!(grammar.starts_with("(root \"Default\")\n"));
+    assert!(grammar.contains(
+      "\n(context \"String\"\n  (kind text)\n  (escape \"\\\\\"))\n"
+    ));
+    assert!(grammar.contains("\n(context \"Comment\"\n  (kind comment)"));
+    assert!(grammar
+      .contains("(encloser \"_LINE_COMMENT_\" \";\" \"\\n\" \"Comment\")\n"));
+    let graph = read_grammar(&grammar).unwrap();
+    assert_eq!(write_grammar(&graph), grammar);
+    let text = "(defn f [x] ; comment\n  ^:private #{'x @y \"a \\\" b\"})";
+    assert_eq!(
+      Parser::new(graph, text)
+        .read_all()
+        .into_iter()
+        .map(|tree| RawSexp::from(tree.unwrap()).to_string())
+        .collect::<Vec<_>>(),
+      Parser::new(clj_graph(), text)
+        .read_all()
+        .into_iter()
+        .map(|tree| RawSexp::from(tree.unwrap()).to_string())
+        .collect::<Vec<_>>(),
+    );
+  }
+
+  #[test]
+  fn clj_graph_analysis() {
+    let ambiguities = clj_graph().analyze();
+    assert_eq!(
+      ambiguities,
+      vec![Ambiguity {
+        context: CljContext::Default,
+        kind: AmbiguityKind::PrefixMarker {
+          shorter: "_UNQUOTE_".to_string(),
+          longer: "_UNQUOTE_SPLICE_".to_string(),
+          shadowed: true,
+        },
+        example: "~@x".to_string(),
+      }]
+    );
+    assert_eq!(
+      parse_trees(&ambiguities[0].example)[0],
+      Sexp::Inner(
+        Operator(Unquote),
+        vec![Sexp::Inner(
+          Operator(Deref),
+          vec![Sexp::Leaf((), "x".to_string())]
+        )]
+      )
+    );
+  }
+
+  #[test]
+  fn clj_reader_macros() {
+    let leaf = |text: &str| Sexp::Leaf((), text.to_string());
+    let trees = Parser::new(
+      clj_graph_with_reader_macros(),
+      "(str \\a \\λ \\newline \\() #inst \"2020\"\n(a #?(:clj 1 :cljs 2) b)\n#?(:cljs 3) x",
+    )
+    .read_all()
+    .into_iter()
+    .map(|tree| tree.unwrap().into())
+    .collect::<Vec<SyntaxTree<CljEncloser, CljOperator>>>();
+    assert_eq!(
+      trees,
+      vec![
+        Sexp::Inner(
+          Encloser(List),
+          vec![
+            leaf("str"),
+            leaf("\\a"),
+            leaf("\\λ"),
+            leaf("\\newline"),
+            leaf("\\(")
+          ]
+        ),
+        Sexp::Inner(
+          Operator(TaggedLiteral),
+          vec![
+            leaf("inst"),
+            Sexp::Inner(Encloser(String), vec![leaf("2020")])
+          ]
+        ),
+        Sexp::Inner(Encloser(List), vec![leaf("a"), leaf("1"), leaf("b")]),
+        leaf("x"),
+      ]
+    );
+    assert_eq!(clj_graph_with_reader_macros().validate(), Ok(()));
+    assert_eq!(
+      clj_graph_with_reader_macros()
+        .analyze()
+        .into_iter()
+        .filter(|ambiguity| !matches!(
+          &ambiguity.kind,
+          AmbiguityKind::PrefixMarker { shorter, shadowed: false, .. }
+            if shorter == "_TAGGED_LITERAL_"
+        ))
+        .collect::<Vec<_>>(),
+      clj_graph().analyze()
+    );
+    assert_eq!(
+      Parser::new(clj_graph_with_reader_macros(), "#foo/bar [1]")
+        .read_next_sexp(),
+      Ok(Some(RawSexp::inner(vec![
+        RawSexp::leaf("_TAGGED_LITERAL_".to_string()),
+        RawSexp::leaf("foo/bar".to_string()),
+        RawSexp::inner(vec![
+          RawSexp::leaf("_VECTOR_".to_string()),
+          RawSexp::leaf("1".to_string())
+        ])
+      ])))
+    );
+    assert_eq!(
+      Parser::new(clj_graph_with_reader_macros(), "#?[1]").read_next(),
+      Err(ParseError::ReaderMacroError {
+        marker: "#?".to_string(),
+        message: "expected a list of features and forms".to_string()
+      })
+    );
+  }
+
+  #[test]
+  fn clj_reader_macro_markers() {
+    let mut doc = Document::from_string_with_syntax(
+      clj_graph_with_reader_macros(),
+      "(str \\() #?(:clj 1)".to_string(),
+    )
+    .unwrap();
+    assert_eq!(doc.matching_marker(0), Some(7..8));
+    assert_eq!(doc.matching_marker(7), Some(0..1));
+    assert_eq!(doc.marker_at(6), None);
+    let kinds = |doc: &Document<_, _, _>| {
+      doc
+        .tokens()
+        .iter()
+        .map(|token| (token.range.clone(), token.kind.clone()))
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(
+      kinds(&doc)[3..],
+      [
+        (5..7, TokenKind::Leaf(CljContext::Default)),
+        (7..8, TokenKind::Marker(MarkerKind::Closer(List))),
+        (8..9, TokenKind::Whitespace),
+        (9..16, TokenKind::Discarded),
+        (16..17, TokenKind::Whitespace),
+        (17..18, TokenKind::Leaf(CljContext::Default)),
+        (18..19, TokenKind::Discarded),
+      ]
+    );
+    assert_eq!(doc.version(), 0);
+    let _ = doc.apply_edit(&TextEdit::new(0..0, "("));
+    assert_eq!(doc.version(), 1);
+    assert_eq!(doc.matching_marker(0), None);
+    assert_eq!(doc.matching_marker(1), Some(8..9));
+    assert_eq!(doc.marker_at(7), None);
+    assert_eq!(kinds(&doc)[4], (6..8, TokenKind::Leaf(CljContext::Default)));
+    let doc = Document::from_string_with_syntax(
+      clj_graph_with_reader_macros(),
+      "#inst \"x\"".to_string(),
+    )
+    .unwrap();
+    assert_eq!(
+      doc.marker_at(0).map(|marker| (marker.range, marker.kind)),
+      Some((0..1, MarkerKind::Operator(TaggedLiteral)))
+    );
+    assert_eq!(kinds(&doc)[1], (1..5, TokenKind::Leaf(CljContext::Default)));
+  }
+
+  #[test]
+  fn clj_syntax_switch() {
+    let leaf = |text: &str| Sexp::Leaf((), text.to_string());
+    let text = "(use-syntax :reader-macros)\n#?(:clj 1)\n";
+    let mut parser = Parser::new(clj_graph(), text);
+    assert_eq!(
+      parser.read_next().unwrap().map(SyntaxTree::from),
+      Some(Sexp::Inner(
+        Encloser(List),
+        vec![leaf("use-syntax"), leaf(":reader-macros")]
+      ))
+    );
+    parser.replace_syntax_graph(clj_graph_with_reader_macros());
+    assert_eq!(
+      parser.read_next().unwrap().map(SyntaxTree::from),
+      Some(leaf("1"))
+    );
+    assert_eq!(parser.read_next(), Ok(None));
+
+    let trees = |document: &Document<_, _, _>| {
+      document
+        .syntax_trees()
+        .iter()
+        .map(|tree| SyntaxTree::from(tree.clone()))
+        .collect::<Vec<_>>()
+    };
+    let mut document = Document::from_text_with_syntax(clj_graph(), text)
+      .unwrap()
+      .into_owned();
+    assert_eq!(trees(&document).len(), 3);
+    document
+      .switch_syntax_graph(1, clj_graph_with_reader_macros())
+      .unwrap();
+    assert_eq!(
+      document
+        .syntax_regions()
+        .iter()
+        .map(|region| region.start)
+        .collect::<Vec<_>>(),
+      vec![27]
+    );
+    assert!(document
+      .syntax_graph_at(0)
+      .get_context(&CljContext::Default)
+      .reader_macros()
+      .is_empty());
+    assert_eq!(trees(&document)[1], leaf("1"));
+
+    document
+      .apply_edit(&TextEdit::new(0..0, "(ns a)\n#?(:clj 0)\n"))
+      .unwrap();
+    document
+      .apply_edit(&TextEdit::new(
+        document.text.len()..document.text.len(),
+        "#?(:clj 2)",
+      ))
+      .unwrap();
+    assert_eq!(document.syntax_regions()[0].start, 45);
+    assert_eq!(
+      trees(&document)[3..],
+      [
+        Sexp::Inner(
+          Encloser(List),
+          vec![leaf("use-syntax"), leaf(":reader-macros")]
+        ),
+        leaf("1"),
+        leaf("2"),
+      ]
+    );
+    assert_eq!(trees(&document)[1], leaf("#?"));
+
+    let reader_macros = clj_graph_with_reader_macros()
+      .get_context(&CljContext::Default)
+      .reader_macros()
+      .to_vec();
+    document
+      .extend_syntax_graph(0, |graph| {
+        for reader_macro in reader_macros {
+          graph
+            .add_reader_macro(&CljContext::Default, reader_macro)
+            .unwrap();
+        }
+      })
+      .unwrap();
+    assert_eq!(trees(&document)[1], leaf("0"));
+    assert_eq!(trees(&document).len(), 5);
+
+    let mut document =
+      Document::from_text_with_syntax(clj_graph(), "(a)\n(str)").unwrap();
+    document
+      .switch_syntax_graph(1, clj_graph_with_reader_macros())
+      .unwrap();
+    document.apply_edit(&TextEdit::new(8..8, " \\(")).unwrap();
+    assert_eq!(
+      document.tokens().last(),
+      Some(&Token {
+        range: 11..12,
+        kind: TokenKind::Marker(MarkerKind::Closer(List))
+      })
+    );
+    assert_eq!(
+      document.tokens()[document.tokens().len() - 2],
+      Token {
+        range: 9..11,
+        kind: TokenKind::Leaf(CljContext::Default)
+      }
+    );
+    assert_eq!(document.matching_marker(4), Some(11..12));
+    assert_eq!(
+      document
+        .parinfer(ParinferMode::Paren, None)
+        .map(|result| result.edits),
+      Ok(vec![])
+    );
+  }
+
+  #[test]
+  fn edn_dialect() {
+    let graph = edn_graph();
+    assert_eq!(
+      graph.enclosers().count(),
+      clj_graph().enclosers().count() - 2
+    );
+    assert_eq!(
+      graph.get_context(&CljContext::Default).operators(),
+      &[FormComment]
+    );
+    assert_eq!(graph.analyze(), vec![]);
+    let trees = Parser::new(graph, "#(x) @a #_ b [c]")
+      .read_all()
+      .into_iter()
+      .map(|tree| SyntaxTree::from(tree.unwrap()))
+      .collect::<Vec<_>>();
+    let leaf = |text: &str| Sexp::Leaf((), text.to_string());
+    assert_eq!(
+      trees,
+      vec![
+        leaf("#"),
+        Sexp::Inner(Encloser(List), vec![leaf("x")]),
+        leaf("@a"),
+        Sexp::Inner(Operator(FormComment), vec![leaf("b")]),
+        Sexp::Inner(Encloser(Vector), vec![leaf("c")]),
+      ]
+    );
+  }
 }
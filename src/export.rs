@@ -0,0 +1,163 @@
+use std::{
+  fmt::{Debug, Write},
+  hash::Hash,
+};
+
+use crate::{Encloser, Operator, SyntaxContext, SyntaxGraph};
+
+fn context_name<C: Debug>(context_tag: &C) -> String {
+  let debug = format!("{context_tag:?}");
+  debug
+    .strip_prefix('"')
+    .and_then(|name| name.strip_suffix('"'))
+    .map(str::to_string)
+    .unwrap_or(debug)
+}
+
+fn dot_string(s: &str) -> String {
+  format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn ebnf_name(name: &str) -> String {
+  name
+    .chars()
+    .map(|c| if c.is_alphanumeric() { c } else { '_' })
+    .collect()
+}
+
+fn terminal(s: &str) -> String {
+  format!("{s:?}")
+}
+
+fn marker_label(id: &str, markers: &str) -> String {
+  if id.is_empty() {
+    markers.to_string()
+  } else {
+    format!("{id} {markers}")
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  pub(crate) fn sorted_contexts(&self) -> Vec<(&C, &SyntaxContext<E, O>)> {
+    let mut contexts = self.contexts().collect::<Vec<_>>();
+    contexts.sort_by_key(|(context_tag, _)| {
+      (*context_tag != self.root(), context_name(*context_tag))
+    });
+    contexts
+  }
+  pub fn to_dot(&self) -> String {
+    let mut dot = "digraph syntax {\n".to_string();
+    for (context_tag, _) in self.sorted_contexts() {
+      let shape = if context_tag == self.root() {
+        "doublecircle"
+      } else {
+        "circle"
+      };
+      writeln!(
+        dot,
+        "  {} [shape={shape}];",
+        dot_string(&context_name(context_tag))
+      )
+      .unwrap();
+    }
+    for (context_tag, context) in self.sorted_contexts() {
+      let from = dot_string(&context_name(context_tag));
+      for encloser in context.enclosers() {
+        let label = marker_label(
+          encloser.id_str(),
+          &format!(
+            "{} … {}",
+            encloser.opening_encloser_str(),
+            encloser.closing_encloser_str()
+          ),
+        );
+        writeln!(
+          dot,
+          "  {from} -> {} [label={}];",
+          dot_string(&context_name(self.get_encloser_context_tag(encloser))),
+          dot_string(&label)
+        )
+        .unwrap();
+      }
+      for operator in context.operators() {
+        let label = marker_label(
+          operator.id_str(),
+          &format!(
+            "{} ({}, {})",
+            operator.op_str(),
+            operator.left_args(),
+            operator.right_args()
+          ),
+        );
+        writeln!(
+          dot,
+          "  {from} -> {} [label={}, style=dashed];",
+          dot_string(&context_name(self.get_operator_context_tag(operator))),
+          dot_string(&label)
+        )
+        .unwrap();
+      }
+    }
+    dot.push_str("}\n");
+    dot
+  }
+  pub fn to_ebnf(&self) -> String {
+    let mut ebnf = String::new();
+    for (context_tag, context) in self.sorted_contexts() {
+      let name = ebnf_name(&context_name(context_tag));
+      if !ebnf.is_empty() {
+        ebnf.push('\n');
+      }
+      writeln!(ebnf, "{name} = {{ {name}_form }} ;").unwrap();
+      let mut alternatives = vec![format!("{name}_leaf")];
+      for encloser in context.enclosers() {
+        let target =
+          ebnf_name(&context_name(self.get_encloser_context_tag(encloser)));
+        alternatives.push(format!(
+          "{} , {target} , {}",
+          terminal(encloser.opening_encloser_str()),
+          terminal(encloser.closing_encloser_str())
+        ));
+      }
+      for operator in context.operators() {
+        let target = format!(
+          "{}_form",
+          ebnf_name(&context_name(self.get_operator_context_tag(operator)))
+        );
+        let args = |count: usize| vec![target.clone(); count];
+        alternatives.push(
+          args(operator.left_args())
+            .into_iter()
+            .chain(std::iter::once(terminal(operator.op_str())))
+            .chain(args(operator.right_args()))
+            .collect::<Vec<_>>()
+            .join(" , "),
+        );
+      }
+      let indent = " ".repeat(name.len() + 6);
+      writeln!(
+        ebnf,
+        "{name}_form = {} ;",
+        alternatives.join(&format!("\n{indent}| "))
+      )
+      .unwrap();
+      let whitespace = context
+        .whitespace_chars()
+        .iter()
+        .map(|whitespace| terminal(whitespace))
+        .collect::<Vec<_>>();
+      let mut leaf = "? any text up to ".to_string();
+      if !whitespace.is_empty() {
+        write!(leaf, "whitespace ({}), ", whitespace.join(" | ")).unwrap();
+      }
+      leaf.push_str("a marker or a closer");
+      if let Some(escape_char) = context.escape_char() {
+        write!(leaf, ", escaped by {}", terminal(escape_char)).unwrap();
+      }
+      writeln!(ebnf, "{name}_leaf = {leaf} ? ;").unwrap();
+    }
+    ebnf
+  }
+}
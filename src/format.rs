@@ -0,0 +1,277 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash};
+
+use crate::{
+  pretty::{Doc, PrettyPrinter},
+  syntax::EncloserOrOperator,
+  Encloser, Operator, Sexp, SyntaxContext, SyntaxGraph, SyntaxTree,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatLayout {
+  Default,
+  Block { args: usize, indent: usize },
+  Pairs { align: bool },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatOverride {
+  pub layout: FormatLayout,
+  pub children: HashMap<usize, FormatOverride>,
+}
+
+impl FormatOverride {
+  pub fn new(layout: FormatLayout) -> Self {
+    Self {
+      layout,
+      children: HashMap::new(),
+    }
+  }
+  pub fn with_child(
+    mut self,
+    child_index: usize,
+    child_override: FormatOverride,
+  ) -> Self {
+    self.children.insert(child_index, child_override);
+    self
+  }
+}
+
+#[derive(Clone, Debug)]
+pub enum FormatRule<E: Encloser, O: Operator> {
+  Head {
+    encloser_or_operator: EncloserOrOperator<E, O>,
+    head: String,
+    format: FormatOverride,
+  },
+  Form {
+    encloser_or_operator: EncloserOrOperator<E, O>,
+    format: FormatOverride,
+  },
+  Custom(fn(&SyntaxTree<E, O>) -> Option<FormatOverride>),
+}
+
+impl<E: Encloser, O: Operator> FormatRule<E, O> {
+  pub fn format_override(
+    &self,
+    tree: &SyntaxTree<E, O>,
+  ) -> Option<FormatOverride> {
+    let Sexp::Inner(tree_encloser_or_operator, children) = tree else {
+      return None;
+    };
+    match self {
+      FormatRule::Head {
+        encloser_or_operator,
+        head,
+        format,
+      } => {
+        let head_matches = matches!(
+          children.first(),
+          Some(Sexp::Leaf(_, leaf)) if leaf == head
+        );
+        (encloser_or_operator == tree_encloser_or_operator && head_matches)
+          .then(|| format.clone())
+      }
+      FormatRule::Form {
+        encloser_or_operator,
+        format,
+      } => (encloser_or_operator == tree_encloser_or_operator)
+        .then(|| format.clone()),
+      FormatRule::Custom(rule) => rule(tree),
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+pub struct FormatRules<E: Encloser, O: Operator> {
+  rules: Vec<FormatRule<E, O>>,
+}
+
+impl<E: Encloser, O: Operator> Default for FormatRules<E, O> {
+  fn default() -> Self {
+    Self { rules: vec![] }
+  }
+}
+
+impl<E: Encloser, O: Operator> FormatRules<E, O> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn with_rule(mut self, rule: FormatRule<E, O>) -> Self {
+    self.rules.push(rule);
+    self
+  }
+  pub fn with_head_rule(
+    self,
+    encloser_or_operator: EncloserOrOperator<E, O>,
+    head: impl Into<String>,
+    format: FormatOverride,
+  ) -> Self {
+    self.with_rule(FormatRule::Head {
+      encloser_or_operator,
+      head: head.into(),
+      format,
+    })
+  }
+  pub fn with_form_rule(
+    self,
+    encloser_or_operator: EncloserOrOperator<E, O>,
+    format: FormatOverride,
+  ) -> Self {
+    self.with_rule(FormatRule::Form {
+      encloser_or_operator,
+      format,
+    })
+  }
+  pub fn with_custom_rule(
+    self,
+    rule: fn(&SyntaxTree<E, O>) -> Option<FormatOverride>,
+  ) -> Self {
+    self.with_rule(FormatRule::Custom(rule))
+  }
+  pub fn rules(&self) -> &[FormatRule<E, O>] {
+    &self.rules
+  }
+  pub fn format_override(
+    &self,
+    tree: &SyntaxTree<E, O>,
+  ) -> Option<FormatOverride> {
+    self
+      .rules
+      .iter()
+      .find_map(|rule| rule.format_override(tree))
+  }
+}
+
+pub struct Formatter<
+  'g,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  printer: PrettyPrinter<'g, C, E, O>,
+  rules: FormatRules<E, O>,
+}
+
+impl<'g, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  Formatter<'g, C, E, O>
+{
+  pub fn new(
+    syntax_graph: &'g SyntaxGraph<C, E, O>,
+    width: usize,
+    rules: FormatRules<E, O>,
+  ) -> Self {
+    Self {
+      printer: PrettyPrinter::new(syntax_graph, width),
+      rules,
+    }
+  }
+  pub fn rules(&self) -> &FormatRules<E, O> {
+    &self.rules
+  }
+  fn layout_body(
+    encloser: &E,
+    context: &SyntaxContext<E, O>,
+    layout: FormatLayout,
+    mut children: Vec<Doc>,
+  ) -> Doc {
+    if !context.is_whitespace(" ") {
+      return PrettyPrinter::<C, E, O>::default_body(
+        encloser, context, children,
+      );
+    }
+    match layout {
+      FormatLayout::Block { args, indent } if children.len() > args + 1 => {
+        let body = children.split_off(args + 1);
+        let head =
+          PrettyPrinter::<C, E, O>::join_with(children, Doc::text(" "));
+        Doc::nest(
+          indent,
+          PrettyPrinter::<C, E, O>::join(
+            context,
+            std::iter::once(head).chain(body).collect(),
+            true,
+          ),
+        )
+      }
+      FormatLayout::Pairs { align } => {
+        let mut pairs = vec![];
+        let mut children = children.into_iter();
+        while let Some(key) = children.next() {
+          pairs.push((key, children.next()));
+        }
+        let key_widths = pairs
+          .iter()
+          .filter(|(_, value)| value.is_some())
+          .map(|(key, _)| key.flat_width())
+          .collect::<Option<Vec<usize>>>();
+        let max_key_width = key_widths
+          .filter(|_| align)
+          .and_then(|key_widths| key_widths.into_iter().max());
+        let pairs = pairs
+          .into_iter()
+          .map(|(key, value)| match value {
+            None => key,
+            Some(value) => {
+              let separator = match (max_key_width, key.flat_width()) {
+                (Some(max_key_width), Some(key_width)) => Doc::if_break(
+                  Doc::text(" ".repeat(max_key_width - key_width + 1)),
+                  Doc::text(" "),
+                ),
+                _ => Doc::text(" "),
+              };
+              Doc::Concat(vec![key, separator, value])
+            }
+          })
+          .collect();
+        PrettyPrinter::<C, E, O>::default_body(encloser, context, pairs)
+      }
+      _ => PrettyPrinter::<C, E, O>::default_body(encloser, context, children),
+    }
+  }
+  fn tree_doc(
+    &self,
+    tree: &SyntaxTree<E, O>,
+    format_override: Option<FormatOverride>,
+  ) -> Doc {
+    match tree {
+      Sexp::Leaf((), leaf) => Doc::text(leaf),
+      Sexp::Inner(encloser_or_operator, children) => {
+        let FormatOverride {
+          layout,
+          children: mut child_overrides,
+        } = format_override
+          .or_else(|| self.rules.format_override(tree))
+          .unwrap_or(FormatOverride::new(FormatLayout::Default));
+        let children = children
+          .iter()
+          .enumerate()
+          .map(|(i, child)| self.tree_doc(child, child_overrides.remove(&i)))
+          .collect();
+        match encloser_or_operator {
+          EncloserOrOperator::Encloser(encloser) => self.printer.encloser_doc(
+            encloser,
+            children,
+            |context, children| {
+              Self::layout_body(encloser, context, layout, children)
+            },
+          ),
+          EncloserOrOperator::Operator(operator) => {
+            self.printer.operator_doc(operator, children)
+          }
+        }
+      }
+    }
+  }
+  pub fn doc(&self, tree: &SyntaxTree<E, O>) -> Doc {
+    self.tree_doc(tree, None)
+  }
+  pub fn format(&self, tree: &SyntaxTree<E, O>) -> String {
+    self.doc(tree).render(self.printer.width())
+  }
+  pub fn format_all(&self, trees: &[SyntaxTree<E, O>]) -> String {
+    self
+      .printer
+      .top_level_doc(trees.iter().map(|tree| self.doc(tree)).collect())
+      .render(self.printer.width())
+  }
+}
@@ -0,0 +1,578 @@
+use std::{
+  collections::{hash_map::Entry, HashMap, HashSet},
+  fmt::{Debug, Display, Write},
+  hash::Hash,
+  ops::Range,
+  sync::Arc,
+};
+
+use crate::{
+  str_tagged::{
+    OwnedStringTaggedEncloser, OwnedStringTaggedOperator,
+    OwnedStringTaggedSyntaxGraph, StringTaggedSyntaxGraph,
+  },
+  syntax::{ContextKind, EncloserOrOperator, SyntaxGraphError},
+  DocumentSyntaxTree, Encloser, Operator, ParseError, Parser, Sexp,
+  SyntaxGraph,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GrammarErrorKind {
+  Parse(ParseError),
+  MalformedForm(String),
+  UnknownForm(String),
+  MissingRoot,
+  DuplicateRoot,
+  DuplicateDefinition(String),
+  UnknownMember(String),
+  UnusedDefinition(String),
+  Graph(SyntaxGraphError<Arc<str>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrammarError {
+  pub kind: GrammarErrorKind,
+  pub range: Option<Range<usize>>,
+}
+
+impl GrammarError {
+  fn new(kind: GrammarErrorKind, range: Option<Range<usize>>) -> Self {
+    Self { kind, range }
+  }
+  fn malformed(expected: &str, range: &Range<usize>) -> Self {
+    Self::new(
+      GrammarErrorKind::MalformedForm(expected.to_string()),
+      Some(range.clone()),
+    )
+  }
+  pub fn row_and_col(&self, text: &str) -> Option<(usize, usize)> {
+    let start = self.range.as_ref()?.start.min(text.len());
+    let row = text[..start].matches('\n').count();
+    let row_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    Some((row, start - row_start))
+  }
+}
+
+impl Display for GrammarError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use GrammarErrorKind::*;
+    match &self.kind {
+      Parse(parse_error) => write!(f, "couldn't parse grammar: {parse_error}"),
+      MalformedForm(expected) => {
+        write!(f, "malformed form, expected {expected}")
+      }
+      UnknownForm(head) => write!(f, "unknown form \"{head}\""),
+      MissingRoot => write!(f, "grammar has no root context"),
+      DuplicateRoot => write!(f, "grammar has more than one root context"),
+      DuplicateDefinition(name) => {
+        write!(f, "\"{name}\" is defined more than once")
+      }
+      UnknownMember(name) => {
+        write!(f, "\"{name}\" isn't a defined encloser or operator")
+      }
+      UnusedDefinition(name) => {
+        write!(f, "\"{name}\" isn't a member of any context")
+      }
+      Graph(graph_error) => write!(f, "{graph_error}"),
+    }?;
+    if let Some(range) = &self.range {
+      write!(f, " at {}..{}", range.start, range.end)?;
+    }
+    Ok(())
+  }
+}
+
+fn grammar_file_graph() -> StringTaggedSyntaxGraph<'static> {
+  StringTaggedSyntaxGraph::from_descriptions(
+    "root",
+    vec![
+      (
+        "root",
+        vec!["", "STRING", "COMMENT"],
+        None,
+        vec![
+          " ".to_string(),
+          "\n".to_string(),
+          "\t".to_string(),
+          "\r".to_string(),
+        ],
+      ),
+      ("string", vec![], Some("\\".to_string()), vec![]),
+      ("comment", vec![], None, vec![]),
+    ],
+    vec![
+      ("", "(", ")", "root"),
+      ("STRING", "\"", "\"", "string"),
+      ("COMMENT", ";", "\n", "comment"),
+    ],
+    vec![],
+  )
+}
+
+fn quote(s: &str) -> String {
+  let mut quoted = "\"".to_string();
+  for c in s.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      '\t' => quoted.push_str("\\t"),
+      '\r' => quoted.push_str("\\r"),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+fn unescape(s: &str) -> String {
+  let mut unescaped = String::new();
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      unescaped.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('n') => unescaped.push('\n'),
+      Some('t') => unescaped.push('\t'),
+      Some('r') => unescaped.push('\r'),
+      Some(escaped) => unescaped.push(escaped),
+      None => unescaped.push('\\'),
+    }
+  }
+  unescaped
+}
+
+type GrammarTree<'g> = DocumentSyntaxTree<
+  crate::str_tagged::StringTaggedEncloser<'g>,
+  crate::str_tagged::StringTaggedOperator<'g>,
+>;
+
+fn is_comment(tree: &GrammarTree) -> bool {
+  matches!(
+    tree,
+    Sexp::Inner((_, EncloserOrOperator::Encloser(encloser)), _)
+      if encloser.id_str() == "COMMENT"
+  )
+}
+
+fn form_children<'t, 'g>(
+  tree: &'t GrammarTree<'g>,
+) -> Option<Vec<&'t GrammarTree<'g>>> {
+  match tree {
+    Sexp::Inner((_, EncloserOrOperator::Encloser(encloser)), children)
+      if encloser.id_str().is_empty() =>
+    {
+      Some(children.iter().filter(|child| !is_comment(child)).collect())
+    }
+    _ => None,
+  }
+}
+
+fn atom(tree: &GrammarTree) -> Option<String> {
+  match tree {
+    Sexp::Leaf(_, leaf) => Some(leaf.clone()),
+    Sexp::Inner((_, EncloserOrOperator::Encloser(encloser)), children)
+      if encloser.id_str() == "STRING" =>
+    {
+      Some(
+        children
+          .iter()
+          .map(|child| match child {
+            Sexp::Leaf(_, leaf) => unescape(leaf),
+            _ => String::new(),
+          })
+          .collect(),
+      )
+    }
+    _ => None,
+  }
+}
+
+fn atoms(
+  trees: &[&GrammarTree],
+  expected: &str,
+) -> Result<Vec<(String, Range<usize>)>, GrammarError> {
+  trees
+    .iter()
+    .map(|tree| {
+      atom(tree)
+        .map(|atom| (atom, tree.range().clone()))
+        .ok_or_else(|| GrammarError::malformed(expected, tree.range()))
+    })
+    .collect()
+}
+
+fn arg_count(tree: &GrammarTree) -> Result<usize, GrammarError> {
+  atom(tree)
+    .and_then(|atom| atom.parse().ok())
+    .ok_or_else(|| GrammarError::malformed("an argument count", tree.range()))
+}
+
+struct ContextDefinition {
+  name: Arc<str>,
+  range: Range<usize>,
+  kind: ContextKind,
+  whitespace_chars: Vec<String>,
+  escape_char: Option<String>,
+  members: Vec<(String, Range<usize>)>,
+}
+
+enum Definition {
+  Encloser(OwnedStringTaggedEncloser, Arc<str>),
+  Operator(OwnedStringTaggedOperator, Arc<str>),
+}
+
+fn read_context(
+  children: &[&GrammarTree],
+  range: &Range<usize>,
+) -> Result<ContextDefinition, GrammarError> {
+  const EXPECTED: &str = "(context NAME (kind KIND) (whitespace ...) \
+                          (escape ESCAPE) (members ...))";
+  let Some((name, clauses)) = children.split_first() else {
+    return Err(GrammarError::malformed(EXPECTED, range));
+  };
+  let mut context = ContextDefinition {
+    name: atom(name)
+      .ok_or_else(|| GrammarError::malformed("a context name", name.range()))?
+      .into(),
+    range: range.clone(),
+    kind: ContextKind::Code,
+    whitespace_chars: vec![],
+    escape_char: None,
+    members: vec![],
+  };
+  for clause in clauses {
+    let clause_children = form_children(clause)
+      .ok_or_else(|| GrammarError::malformed(EXPECTED, clause.range()))?;
+    let Some((head, values)) = clause_children.split_first() else {
+      return Err(GrammarError::malformed(EXPECTED, clause.range()));
+    };
+    match atom(head).as_deref() {
+      Some("kind") => {
+        context.kind = match atoms(values, "a context kind")?.as_slice() {
+          [(kind, _)] if kind == "code" => ContextKind::Code,
+          [(kind, _)] if kind == "text" => ContextKind::Text,
+          [(kind, _)] if kind == "comment" => ContextKind::Comment,
+          _ => {
+            return Err(GrammarError::malformed(
+              "(kind code|text|comment)",
+              clause.range(),
+            ))
+          }
+        }
+      }
+      Some("whitespace") => context.whitespace_chars.extend(
+        atoms(values, "whitespace strings")?
+          .into_iter()
+          .map(|(whitespace, _)| whitespace),
+      ),
+      Some("escape") => match atoms(values, "an escape string")?.as_slice() {
+        [(escape_char, _)] => context.escape_char = Some(escape_char.clone()),
+        _ => {
+          return Err(GrammarError::malformed(
+            "(escape ESCAPE)",
+            clause.range(),
+          ))
+        }
+      },
+      Some("members") => context
+        .members
+        .extend(atoms(values, "encloser or operator ids")?),
+      _ => return Err(GrammarError::malformed(EXPECTED, clause.range())),
+    }
+  }
+  Ok(context)
+}
+
+fn read_definition(
+  head: &str,
+  children: &[&GrammarTree],
+  range: &Range<usize>,
+) -> Result<Definition, GrammarError> {
+  match (head, children) {
+    ("encloser", [id, opener, closer, context]) => {
+      let [id, opener, closer, context] =
+        atoms(&[id, opener, closer, context], "a string")?
+          .try_into()
+          .unwrap();
+      Ok(Definition::Encloser(
+        OwnedStringTaggedEncloser::new(id.0, opener.0, closer.0),
+        context.0.into(),
+      ))
+    }
+    ("encloser", _) => Err(GrammarError::malformed(
+      "(encloser ID OPENER CLOSER CONTEXT)",
+      range,
+    )),
+    ("operator", [id, operator, left_args, right_args, context]) => {
+      let [id, operator, context] =
+        atoms(&[id, operator, context], "a string")?
+          .try_into()
+          .unwrap();
+      Ok(Definition::Operator(
+        OwnedStringTaggedOperator::new(
+          id.0,
+          operator.0,
+          arg_count(left_args)?,
+          arg_count(right_args)?,
+        ),
+        context.0.into(),
+      ))
+    }
+    _ => Err(GrammarError::malformed(
+      "(operator ID OPERATOR LEFT_ARGS RIGHT_ARGS CONTEXT)",
+      range,
+    )),
+  }
+}
+
+pub fn read_grammar(
+  text: &str,
+) -> Result<OwnedStringTaggedSyntaxGraph, Vec<GrammarError>> {
+  let mut errors = vec![];
+  let mut root: Option<(Arc<str>, Range<usize>)> = None;
+  let mut contexts = vec![];
+  let mut definitions: HashMap<String, (Definition, Range<usize>)> =
+    HashMap::new();
+  let mut parsed_until = 0;
+  for tree in Parser::new(grammar_file_graph(), text).read_all() {
+    let tree = match tree {
+      Ok(tree) => tree,
+      Err(parse_error) => {
+        let start = text[parsed_until..]
+          .find(|c: char| !c.is_whitespace())
+          .map_or(text.len(), |offset| parsed_until + offset);
+        return Err(vec![GrammarError::new(
+          GrammarErrorKind::Parse(parse_error),
+          Some(start..text.len()),
+        )]);
+      }
+    };
+    parsed_until = tree.range().end;
+    if is_comment(&tree) {
+      continue;
+    }
+    let range = tree.range().clone();
+    let Some((head, children)) = form_children(&tree)
+      .as_deref()
+      .and_then(|children| children.split_first())
+      .and_then(|(head, children)| Some((atom(head)?, children.to_vec())))
+    else {
+      errors.push(GrammarError::malformed("a (HEAD ...) form", &range));
+      continue;
+    };
+    let result = match head.as_str() {
+      "root" => match atoms(&children, "a context name") {
+        Ok(names) if names.len() == 1 => {
+          if root.is_some() {
+            errors.push(GrammarError::new(
+              GrammarErrorKind::DuplicateRoot,
+              Some(range.clone()),
+            ));
+          } else {
+            root = Some((names[0].0.as_str().into(), range.clone()));
+          }
+          Ok(())
+        }
+        Ok(_) => Err(GrammarError::malformed("(root CONTEXT)", &range)),
+        Err(error) => Err(error),
+      },
+      "context" => {
+        read_context(&children, &range).map(|context| contexts.push(context))
+      }
+      "encloser" | "operator" => {
+        read_definition(&head, &children, &range).map(|definition| {
+          let id = match &definition {
+            Definition::Encloser(encloser, _) => encloser.id_str(),
+            Definition::Operator(operator, _) => operator.id_str(),
+          }
+          .to_string();
+          match definitions.entry(id) {
+            Entry::Occupied(entry) => errors.push(GrammarError::new(
+              GrammarErrorKind::DuplicateDefinition(entry.key().clone()),
+              Some(range.clone()),
+            )),
+            Entry::Vacant(entry) => {
+              entry.insert((definition, range.clone()));
+            }
+          }
+        })
+      }
+      _ => Err(GrammarError::new(
+        GrammarErrorKind::UnknownForm(head.clone()),
+        Some(range.clone()),
+      )),
+    };
+    if let Err(error) = result {
+      errors.push(error);
+    }
+  }
+  let Some((root, root_range)) = root else {
+    errors.push(GrammarError::new(GrammarErrorKind::MissingRoot, None));
+    return Err(errors);
+  };
+  let mut context_ranges = HashMap::new();
+  let mut members = HashSet::new();
+  let mut builder = OwnedStringTaggedSyntaxGraph::builder(root.clone());
+  for context in contexts {
+    context_ranges.insert(context.name.clone(), context.range.clone());
+    let mut context_builder = crate::builder::ContextBuilder::new()
+      .with_kind(context.kind)
+      .with_whitespace_chars(context.whitespace_chars);
+    if let Some(escape_char) = context.escape_char {
+      context_builder = context_builder.with_escape_char(escape_char);
+    }
+    for (member, member_range) in context.members {
+      members.insert(member.clone());
+      match definitions.get(&member) {
+        Some((Definition::Encloser(encloser, target), _)) => {
+          context_builder =
+            context_builder.with_encloser(encloser.clone(), target.clone())
+        }
+        Some((Definition::Operator(operator, target), _)) => {
+          context_builder =
+            context_builder.with_operator(operator.clone(), target.clone())
+        }
+        None => errors.push(GrammarError::new(
+          GrammarErrorKind::UnknownMember(member),
+          Some(member_range),
+        )),
+      }
+    }
+    builder = builder.with_context(context.name, |_| context_builder);
+  }
+  let mut unused_definitions = definitions
+    .iter()
+    .filter(|(id, _)| !members.contains(*id))
+    .map(|(id, (_, range))| {
+      GrammarError::new(
+        GrammarErrorKind::UnusedDefinition(id.clone()),
+        Some(range.clone()),
+      )
+    })
+    .collect::<Vec<_>>();
+  unused_definitions.sort_by_key(|error| error.range.as_ref().unwrap().start);
+  errors.extend(unused_definitions);
+  let definition_range =
+    |id: &str| definitions.get(id).map(|(_, range)| range.clone());
+  match builder.build() {
+    Ok(graph) if errors.is_empty() => Ok(graph),
+    Ok(_) => Err(errors),
+    Err(graph_errors) => {
+      errors.extend(graph_errors.into_iter().map(|graph_error| {
+        use SyntaxGraphError::*;
+        let range = match &graph_error {
+          MissingRootContext(_) => Some(root_range.clone()),
+          DuplicateContext(context)
+          | DuplicateMarker { context, .. }
+          | UnreachableContext(context)
+          | UndefinedParentContext { context, .. }
+          | ConflictingEscapeChars { context }
+          | ConflictingContextKinds { context } => {
+            context_ranges.get(context).cloned()
+          }
+          UndefinedContext { id, .. }
+          | ConflictingContexts { id, .. }
+          | MissingContext { id }
+          | DuplicateId(id)
+          | EmptyMarker { id }
+          | UnusedTag { id } => definition_range(id),
+        };
+        GrammarError::new(GrammarErrorKind::Graph(graph_error), range)
+      }));
+      Err(errors)
+    }
+  }
+}
+
+pub fn write_grammar<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_graph: &SyntaxGraph<C, E, O>,
+) -> String {
+  let name = |context: &C| {
+    let debug = format!("{context:?}");
+    if debug.starts_with('"') {
+      debug
+    } else {
+      quote(&debug)
+    }
+  };
+  let mut grammar = String::new();
+  writeln!(grammar, "(root {})", name(syntax_graph.root())).unwrap();
+  let mut contexts = syntax_graph.contexts().collect::<Vec<_>>();
+  contexts.sort_by_key(|(context_tag, _)| name(context_tag));
+  for (context_tag, context) in contexts {
+    write!(grammar, "\n(context {}", name(context_tag)).unwrap();
+    match context.kind() {
+      ContextKind::Code => {}
+      ContextKind::Text => write!(grammar, "\n  (kind text)").unwrap(),
+      ContextKind::Comment => write!(grammar, "\n  (kind comment)").unwrap(),
+    }
+    if !context.whitespace_chars().is_empty() {
+      let whitespace_chars = context
+        .whitespace_chars()
+        .iter()
+        .map(|whitespace| quote(whitespace))
+        .collect::<Vec<_>>();
+      write!(grammar, "\n  (whitespace {})", whitespace_chars.join(" "))
+        .unwrap();
+    }
+    if let Some(escape_char) = context.escape_char() {
+      write!(grammar, "\n  (escape {})", quote(escape_char)).unwrap();
+    }
+    let members = context
+      .enclosers()
+      .iter()
+      .map(|encloser| quote(encloser.id_str()))
+      .chain(
+        context
+          .operators()
+          .iter()
+          .map(|operator| quote(operator.id_str())),
+      )
+      .collect::<Vec<_>>();
+    if !members.is_empty() {
+      write!(grammar, "\n  (members {})", members.join(" ")).unwrap();
+    }
+    writeln!(grammar, ")").unwrap();
+  }
+  let mut enclosers = syntax_graph.enclosers().collect::<Vec<_>>();
+  enclosers.sort_by_key(|encloser| encloser.id_str().to_string());
+  if !enclosers.is_empty() {
+    grammar.push('\n');
+  }
+  for encloser in enclosers {
+    writeln!(
+      grammar,
+      "(encloser {} {} {} {})",
+      quote(encloser.id_str()),
+      quote(encloser.opening_encloser_str()),
+      quote(encloser.closing_encloser_str()),
+      name(syntax_graph.get_encloser_context_tag(encloser)),
+    )
+    .unwrap();
+  }
+  let mut operators = syntax_graph.operators().collect::<Vec<_>>();
+  operators.sort_by_key(|operator| operator.id_str().to_string());
+  if !operators.is_empty() {
+    grammar.push('\n');
+  }
+  for operator in operators {
+    writeln!(
+      grammar,
+      "(operator {} {} {} {} {})",
+      quote(operator.id_str()),
+      quote(operator.op_str()),
+      operator.left_args(),
+      operator.right_args(),
+      name(syntax_graph.get_operator_context_tag(operator)),
+    )
+    .unwrap();
+  }
+  grammar
+}
@@ -0,0 +1,192 @@
+use std::{collections::HashMap, fmt::Debug, hash::Hash, ops::Range};
+
+use crate::{
+  document::{Document, InvalidDocumentCharPos, TextEdit},
+  syntax::EncloserOrOperator,
+  DocumentSyntaxTree, Encloser, Operator, Sexp,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndentStyle {
+  AlignWithFirstElement,
+  AlignWithFirstArgument,
+  Fixed(usize),
+}
+
+enum Position<'t, E: Encloser, O: Operator> {
+  TopLevel,
+  InEncloser {
+    range: &'t Range<usize>,
+    encloser: &'t E,
+    children: &'t [DocumentSyntaxTree<E, O>],
+  },
+  InLeaf,
+}
+
+#[derive(Clone, Debug)]
+pub struct IndentRules<E: Encloser> {
+  default_style: IndentStyle,
+  encloser_styles: HashMap<E, IndentStyle>,
+  body_heads: HashMap<String, usize>,
+}
+
+impl<E: Encloser> Default for IndentRules<E> {
+  fn default() -> Self {
+    Self::new(IndentStyle::AlignWithFirstArgument)
+  }
+}
+
+impl<E: Encloser> IndentRules<E> {
+  pub fn new(default_style: IndentStyle) -> Self {
+    Self {
+      default_style,
+      encloser_styles: HashMap::new(),
+      body_heads: HashMap::new(),
+    }
+  }
+  pub fn with_encloser_style(
+    mut self,
+    encloser: E,
+    style: IndentStyle,
+  ) -> Self {
+    self.encloser_styles.insert(encloser, style);
+    self
+  }
+  pub fn with_body_head(
+    mut self,
+    head: impl Into<String>,
+    indent: usize,
+  ) -> Self {
+    self.body_heads.insert(head.into(), indent);
+    self
+  }
+  pub fn encloser_style(&self, encloser: &E) -> IndentStyle {
+    self
+      .encloser_styles
+      .get(encloser)
+      .copied()
+      .unwrap_or(self.default_style)
+  }
+  pub fn body_indent(&self, head: &str) -> Option<usize> {
+    self.body_heads.get(head).copied()
+  }
+  fn position<O: Operator>(
+    trees: &[DocumentSyntaxTree<E, O>],
+    index: usize,
+  ) -> Position<'_, E, O> {
+    let mut position = Position::TopLevel;
+    let mut trees = trees;
+    while let Some(tree) = trees
+      .iter()
+      .find(|tree| tree.range().start < index && index < tree.range().end)
+    {
+      match tree {
+        Sexp::Leaf(_, _) => return Position::InLeaf,
+        Sexp::Inner((range, encloser_or_operator), children) => {
+          if let EncloserOrOperator::Encloser(encloser) = encloser_or_operator {
+            position = Position::InEncloser {
+              range,
+              encloser,
+              children,
+            };
+          }
+          trees = children;
+        }
+      }
+    }
+    position
+  }
+  pub(crate) fn anchored_indent_for_line<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    O: Operator,
+  >(
+    &self,
+    document: &Document<C, E, O>,
+    row: usize,
+  ) -> Result<Option<(usize, usize)>, InvalidDocumentCharPos> {
+    let line_start = document.row_and_col_to_index(row, 0)?;
+    let content_start = line_start
+      + document.text[line_start..]
+        .find(|c| c != ' ' && c != '\t')
+        .unwrap_or(document.text.len() - line_start);
+    let (range, encloser, children) =
+      match Self::position(document.syntax_trees(), content_start) {
+        Position::TopLevel => return Ok(Some((row, 0))),
+        Position::InLeaf => return Ok(None),
+        Position::InEncloser {
+          range,
+          encloser,
+          children,
+        } => (range, encloser, children),
+      };
+    let position = |index: usize| document.index_to_row_and_col(index).unwrap();
+    let (opener_row, opener_col) = position(range.start);
+    let element_col = opener_col + encloser.opening_encloser_str().len();
+    Ok(Some(match self.encloser_style(encloser) {
+      IndentStyle::AlignWithFirstElement => (opener_row, element_col),
+      IndentStyle::Fixed(indent) => (opener_row, opener_col + indent),
+      IndentStyle::AlignWithFirstArgument => match children {
+        [Sexp::Leaf(head_range, head), ..]
+          if head_range.start < content_start
+            && self.body_indent(head).is_some() =>
+        {
+          (opener_row, opener_col + self.body_indent(head).unwrap())
+        }
+        [head, first_argument, ..]
+          if first_argument.range().start < content_start
+            && position(head.range().start).0
+              == position(first_argument.range().start).0 =>
+        {
+          position(first_argument.range().start)
+        }
+        _ => (opener_row, element_col),
+      },
+    }))
+  }
+  pub fn indent_for_line<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    O: Operator,
+  >(
+    &self,
+    document: &Document<C, E, O>,
+    row: usize,
+  ) -> Result<Option<usize>, InvalidDocumentCharPos> {
+    self
+      .anchored_indent_for_line(document, row)
+      .map(|indent| indent.map(|(_, col)| col))
+  }
+  pub fn reindent_rows<
+    C: Clone + Debug + PartialEq + Eq + Hash,
+    O: Operator,
+  >(
+    &self,
+    document: &Document<C, E, O>,
+    rows: std::ops::Range<usize>,
+  ) -> Result<Vec<TextEdit>, InvalidDocumentCharPos> {
+    let mut row_shifts: HashMap<usize, isize> = HashMap::new();
+    let mut edits = vec![];
+    for row in rows {
+      let line_start = document.row_and_col_to_index(row, 0)?;
+      let line = document.text[line_start..].split('\n').next().unwrap_or("");
+      let current_indent =
+        line.len() - line.trim_start_matches([' ', '\t']).len();
+      if current_indent == line.trim_end_matches('\r').len() {
+        continue;
+      }
+      if let Some((anchor_row, col)) =
+        self.anchored_indent_for_line(document, row)?
+      {
+        let shift = row_shifts.get(&anchor_row).copied().unwrap_or(0);
+        let indent = (col as isize + shift).max(0) as usize;
+        if indent != current_indent || line[..current_indent].contains('\t') {
+          row_shifts.insert(row, indent as isize - current_indent as isize);
+          edits.push(TextEdit::new(
+            line_start..line_start + current_indent,
+            " ".repeat(indent),
+          ));
+        }
+      }
+    }
+    Ok(edits)
+  }
+}
@@ -0,0 +1,57 @@
+use crate::{reader_macro::ReaderMacro, Encloser, Operator, SyntaxContext};
+
+pub(crate) enum Lexeme<'g, E: Encloser, O: Operator> {
+  Escape,
+  Whitespace,
+  AwaitedCloser,
+  ReaderMacro(&'g ReaderMacro<E, O>),
+  Opener(&'g E),
+  Operator(&'g O),
+  UnexpectedCloser(&'g E),
+  Terminal,
+}
+
+impl<'g, E: Encloser, O: Operator> Lexeme<'g, E, O> {
+  pub(crate) fn lex(
+    context: &'g SyntaxContext<E, O>,
+    awaited_closer: Option<&str>,
+    remaining_text: &str,
+    grapheme: &str,
+  ) -> Self {
+    if context.escape_char() == Some(grapheme) {
+      return Self::Escape;
+    }
+    if context.is_whitespace(grapheme) {
+      return Self::Whitespace;
+    }
+    if awaited_closer.is_some_and(|closer| remaining_text.starts_with(closer)) {
+      return Self::AwaitedCloser;
+    }
+    if let Some(reader_macro) =
+      context.reader_macros().iter().find(|reader_macro| {
+        !reader_macro.marker().is_empty()
+          && remaining_text.starts_with(reader_macro.marker())
+      })
+    {
+      return Self::ReaderMacro(reader_macro);
+    }
+    if let Some(encloser) = context.enclosers().iter().find(|encloser| {
+      remaining_text.starts_with(encloser.opening_encloser_str())
+    }) {
+      return Self::Opener(encloser);
+    }
+    if let Some(operator) = context
+      .operators()
+      .iter()
+      .find(|operator| remaining_text.starts_with(operator.op_str()))
+    {
+      return Self::Operator(operator);
+    }
+    if let Some(encloser) = context.enclosers().iter().find(|encloser| {
+      remaining_text.starts_with(encloser.closing_encloser_str())
+    }) {
+      return Self::UnexpectedCloser(encloser);
+    }
+    Self::Terminal
+  }
+}
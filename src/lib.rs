@@ -1,36 +1,71 @@
+pub mod analysis;
 mod ast;
+pub mod builder;
+pub mod compose;
+pub mod desugar;
+pub mod diff;
 pub mod document;
 pub mod examples;
+pub mod export;
+pub mod format;
+pub mod grammar;
+pub mod indent;
+mod lex;
+pub mod markers;
+pub mod merge;
+pub mod outline;
+pub mod parinfer;
 mod parse;
 mod parser;
+pub mod pretty;
+pub mod query;
+pub mod reader_macro;
+mod resugar;
+pub mod rewrite;
 pub mod str_tagged;
 pub mod syntax;
+pub mod visit;
+pub mod zipper;
 pub use ast::DocumentSyntaxTree;
 pub use ast::RawSexp;
 pub use ast::Sexp;
 pub use ast::SyntaxTree;
 pub use parse::ParseError;
 pub use parser::Parser;
+pub use resugar::ResugarError;
+pub use sse_derive::{Encloser, Operator, SyntaxGraph};
+pub use syntax::ContextKind;
 pub use syntax::Encloser;
 pub use syntax::Operator;
 pub use syntax::SyntaxContext;
 pub use syntax::SyntaxGraph;
+pub use syntax::SyntaxGraphError;
 
 #[cfg(test)]
 mod core_tests {
   use unicode_segmentation::UnicodeSegmentation;
 
   use crate::{
+    analysis::{Ambiguity, AmbiguityKind},
     ast::RawSexp,
-    document::{Document, InvalidDocumentCharPos, InvalidDocumentIndex},
+    compose::ClashResolution,
+    document::{
+      Document, EditError, FoldingRange, InvalidDocumentCharPos,
+      InvalidDocumentIndex, TextEdit,
+    },
     examples::basic::{
       sexp_graph, standard_sexp_whitespace_chars, SexpEncloser,
     },
+    grammar::{read_grammar, GrammarError, GrammarErrorKind},
+    markers::{Marker, MarkerKind, TokenKind},
+    reader_macro::ReaderMacro,
     str_tagged::{
+      OwnedStringTaggedDocument, OwnedStringTaggedSyntaxGraph,
       StringTaggedEncloser, StringTaggedOperator, StringTaggedSyntaxGraph,
     },
-    syntax::EncloserOrOperator,
-    DocumentSyntaxTree, ParseError, Parser,
+    syntax::{ContextKind, EncloserOrOperator},
+    visit::{Visitor, VisitorMut},
+    DocumentSyntaxTree, ParseError, Parser, Sexp, SyntaxGraphError,
   };
 
   fn leaf(s: String) -> RawSexp {
@@ -257,6 +292,23 @@ mod core_tests {
     );
   }
 
+  #[test]
+  fn top_level_infix_op_between_terminals() {
+    assert_eq!(
+      Parser::new(plus_sexp_graph(), "1 2 3+4 5").read_all_sexps(),
+      vec![
+        Ok(leaf("1".to_string())),
+        Ok(leaf("2".to_string())),
+        Ok(inner(vec![
+          leaf("PLUS".to_string()),
+          leaf("3".to_string()),
+          leaf("4".to_string())
+        ])),
+        Ok(leaf("5".to_string())),
+      ]
+    );
+  }
+
   #[test]
   fn solo_infix_op_in_list() {
     assert_eq!(
@@ -911,4 +963,1018 @@ mod core_tests {
       assert_eq!(doc.row_and_col_to_index(row, col), Ok(i));
     }
   }
+
+  #[test]
+  fn owned_document_outlives_source_text() {
+    let doc = {
+      let text = "(* (+ 1 2) 3)".to_string();
+      Document::from_text_with_syntax(sexp_graph(), &text)
+        .unwrap()
+        .into_owned()
+    };
+    assert_eq!(doc.get_subtree_text(&[0, 1]).unwrap(), "(+ 1 2)");
+  }
+
+  #[test]
+  fn owned_document_edit_reparses() {
+    let mut doc = Document::from_string_with_syntax(
+      sexp_graph(),
+      "(+ 1 2) (* 3 4)".to_string(),
+    )
+    .unwrap();
+    doc.apply_edit(&TextEdit::new(11..12, "(- 5 6)")).unwrap();
+    assert_eq!(doc.text, "(+ 1 2) (* (- 5 6) 4)");
+    assert_eq!(doc.get_subtree_text(&[0]).unwrap(), "(+ 1 2)");
+    assert_eq!(doc.get_subtree_text(&[1, 1]).unwrap(), "(- 5 6)");
+    assert_eq!(
+      doc.get_subtree(&[1]).unwrap().clone(),
+      Parser::new(sexp_graph(), "        (* (- 5 6) 4)")
+        .read_next()
+        .unwrap()
+        .unwrap()
+    );
+  }
+
+  #[test]
+  fn owned_document_edit_updates_lines() {
+    let mut doc = Document::from_string_with_syntax(
+      sexp_graph(),
+      "(* (+ 1 2)\n   3)".to_string(),
+    )
+    .unwrap();
+    doc
+      .apply_edit(&TextEdit::new(3..10, "(+ 1\n      2)"))
+      .unwrap();
+    let fresh =
+      Document::from_text_with_syntax(sexp_graph(), "(* (+ 1\n      2)\n   3)")
+        .unwrap();
+    assert_eq!(doc.text, fresh.text);
+    for i in 0..=doc.text.len() {
+      assert_eq!(doc.index_to_row_and_col(i), fresh.index_to_row_and_col(i));
+    }
+    assert_eq!(doc.row_and_col_to_index(2, 3), Ok(20));
+    doc.apply_edit(&TextEdit::new(7..14, "")).unwrap();
+    assert_eq!(doc.text, "(* (+ 12)\n   3)");
+    assert_eq!(doc.index_to_row_and_col(13), Ok((1, 3)));
+  }
+
+  #[test]
+  fn owned_document_edits_match_fresh_parse() {
+    for (text, range, insertion) in [
+      ("🇸🇺🇸", 0..0, "🇺"),
+      ("🇺🇸🇺🇸 a", 0..4, ""),
+      ("(a\rb)", 3..3, "\n(c)"),
+      ("(a b)", 3..3, "\u{301}"),
+      ("(a b)", 2..2, "\u{301}"),
+      ("(a\u{301} b)", 2..4, ""),
+    ] {
+      let mut doc =
+        Document::from_string_with_syntax(sexp_graph(), text.to_string())
+          .unwrap();
+      doc.apply_edit(&TextEdit::new(range, insertion)).unwrap();
+      let fresh =
+        Document::from_string_with_syntax(sexp_graph(), doc.text.to_string())
+          .unwrap();
+      assert_eq!(doc.syntax_trees(), fresh.syntax_trees(), "{text:?}");
+      assert_eq!(doc.grapheme_indeces(), fresh.grapheme_indeces(), "{text:?}");
+      assert_eq!(doc.newline_indeces(), fresh.newline_indeces(), "{text:?}");
+    }
+  }
+
+  #[test]
+  fn owned_document_edit_reports_parse_errors() {
+    let mut doc =
+      Document::from_string_with_syntax(sexp_graph(), "(+ 1 2) 3".to_string())
+        .unwrap();
+    assert_eq!(
+      doc.apply_edit(&TextEdit::new(8..8, "(")),
+      Err(EditError::Parse(ParseError::EndOfTextWithOpenEncloser(
+        "(".to_string()
+      )))
+    );
+    assert_eq!(doc.get_subtree_text(&[0]).unwrap(), "(+ 1 2)");
+    assert_eq!(doc.apply_edit(&TextEdit::new(10..10, ")")), Ok(()));
+    assert_eq!(doc.get_subtree_text(&[1]).unwrap(), "(3)");
+  }
+
+  #[test]
+  fn owned_document_rejects_invalid_edit_ranges() {
+    let mut doc =
+      Document::from_string_with_syntax(sexp_graph(), "(a é)".to_string())
+        .unwrap();
+    let invalid = Err(EditError::InvalidIndex(InvalidDocumentIndex));
+    assert_eq!(doc.apply_edit(&TextEdit::new(4..5, "x")), invalid);
+    assert_eq!(doc.apply_edit(&TextEdit::new(5..9, "x")), invalid);
+    assert_eq!(
+      doc.apply_edits(&[TextEdit::new(1..3, "b"), TextEdit::new(2..4, "c")]),
+      invalid
+    );
+    assert_eq!(doc.text, "(a é)");
+    assert_eq!(doc.apply_edit(&TextEdit::new(3..5, "x")), Ok(()));
+    assert_eq!(doc.get_subtree_text(&[0, 1]).unwrap(), "x");
+  }
+
+  #[test]
+  fn multi_char_closer_ranges() {
+    let graph = StringTaggedSyntaxGraph::contextless_from_descriptions(
+      standard_sexp_whitespace_chars(),
+      None,
+      vec![("", "(", ")"), (":BLOCK", "<|", "|>")],
+      vec![],
+    );
+    let doc =
+      Document::from_text_with_syntax(graph, "(a <|b c|> d) <|e|>").unwrap();
+    assert_eq!(doc.get_subtree(&[0]).unwrap().range(), &(0..13));
+    assert_eq!(doc.get_subtree(&[0, 1]).unwrap().range(), &(3..10));
+    assert_eq!(doc.get_subtree_text(&[0, 1]).unwrap(), "<|b c|>");
+    assert_eq!(doc.get_subtree(&[1]).unwrap().range(), &(14..19));
+  }
+
+  #[test]
+  fn document_matching_marker() {
+    let doc =
+      Document::from_text_with_syntax(sexp_graph(), "(* (+ 1 2) 3)").unwrap();
+    assert_eq!(doc.matching_marker(0), Some(12..13));
+    assert_eq!(doc.matching_marker(12), Some(0..1));
+    assert_eq!(doc.matching_marker(3), Some(9..10));
+    assert_eq!(doc.matching_marker(9), Some(3..4));
+    assert_eq!(doc.matching_marker(1), None);
+    assert_eq!(doc.matching_marker(2), None);
+  }
+
+  #[test]
+  fn document_matching_multi_char_marker() {
+    let doc =
+      Document::from_text_with_syntax(multi_bracket_graph(), "(union #{1 20})")
+        .unwrap();
+    assert_eq!(doc.matching_marker(7), Some(13..14));
+    assert_eq!(doc.matching_marker(8), Some(13..14));
+    assert_eq!(doc.matching_marker(13), Some(7..9));
+    assert_eq!(doc.get_subtree(&[0, 1]).unwrap().range(), &(7..14));
+  }
+
+  #[test]
+  fn document_matching_operator_marker() {
+    let doc = Document::from_text_with_syntax(plus_sexp_graph(), "(inc 1 + 2)")
+      .unwrap();
+    assert_eq!(doc.matching_marker(7), Some(5..10));
+    assert_eq!(
+      doc.marker_at(7).unwrap().kind,
+      MarkerKind::Operator(StringTaggedOperator::new("PLUS", "+", 1, 1))
+    );
+  }
+
+  #[test]
+  fn unbalanced_document_matching_marker() {
+    let mut doc =
+      Document::from_string_with_syntax(sexp_graph(), "(+ 1 2)".to_string())
+        .unwrap();
+    assert!(doc.apply_edit(&TextEdit::new(0..0, "(")).is_err());
+    assert_eq!(doc.text, "((+ 1 2)");
+    assert_eq!(doc.matching_marker(0), None);
+    assert_eq!(
+      doc.marker_at(0).unwrap().kind,
+      MarkerKind::Opener(SexpEncloser)
+    );
+    assert_eq!(doc.matching_marker(1), Some(7..8));
+    assert_eq!(doc.matching_marker(7), Some(1..2));
+
+    assert!(doc.apply_edit(&TextEdit::new(0..1, ")")).is_err());
+    assert_eq!(doc.text, ")(+ 1 2)");
+    assert_eq!(
+      doc.marker_at(0),
+      Some(Marker {
+        range: 0..1,
+        kind: MarkerKind::Closer(SexpEncloser),
+        partner: None
+      })
+    );
+    assert_eq!(doc.matching_marker(1), Some(7..8));
+  }
+
+  #[test]
+  fn document_tokens() {
+    let doc = Document::from_text_with_syntax(plus_sexp_graph(), "(a \\  1+2)")
+      .unwrap();
+    let plus = StringTaggedOperator::new("PLUS", "+", 1, 1);
+    let paren = StringTaggedEncloser::new("", "(", ")");
+    assert_eq!(
+      doc
+        .tokens()
+        .iter()
+        .map(|token| (token.range.clone(), token.kind.clone()))
+        .collect::<Vec<_>>(),
+      vec![
+        (0..1, TokenKind::Marker(MarkerKind::Opener(paren.clone()))),
+        (1..2, TokenKind::Leaf("")),
+        (2..3, TokenKind::Whitespace),
+        (3..5, TokenKind::Leaf("")),
+        (5..6, TokenKind::Whitespace),
+        (6..7, TokenKind::Leaf("")),
+        (7..8, TokenKind::Marker(MarkerKind::Operator(plus))),
+        (8..9, TokenKind::Leaf("")),
+        (9..10, TokenKind::Marker(MarkerKind::Closer(paren))),
+      ]
+    );
+  }
+
+  #[test]
+  fn document_folding_ranges() {
+    let doc = Document::from_text_with_syntax(
+      multi_bracket_graph(),
+      "(a [b c]\n   {d\n    e})\n(f)",
+    )
+    .unwrap();
+    assert_eq!(
+      doc.folding_ranges(),
+      vec![
+        FoldingRange {
+          range: 0..22,
+          start_row: 0,
+          end_row: 2,
+          encloser: StringTaggedEncloser::new("", "(", ")")
+        },
+        FoldingRange {
+          range: 12..21,
+          start_row: 1,
+          end_row: 2,
+          encloser: StringTaggedEncloser::new(":CURLY", "{", "}")
+        }
+      ]
+    );
+  }
+
+  fn visit_test_sexp() -> RawSexp {
+    Parser::new(sexp_graph(), "(a (b c) () d)")
+      .read_next_sexp()
+      .unwrap()
+      .unwrap()
+  }
+
+  #[test]
+  fn sexp_pre_and_post_order() {
+    let sexp = visit_test_sexp();
+    assert_eq!(
+      sexp
+        .pre_order()
+        .map(|(path, node)| (path, node.to_string()))
+        .collect::<Vec<_>>(),
+      vec![
+        (vec![], "(a (b c) () d)".to_string()),
+        (vec![0], "a".to_string()),
+        (vec![1], "(b c)".to_string()),
+        (vec![1, 0], "b".to_string()),
+        (vec![1, 1], "c".to_string()),
+        (vec![2], "()".to_string()),
+        (vec![3], "d".to_string()),
+      ]
+    );
+    assert_eq!(
+      sexp.post_order().map(|(path, _)| path).collect::<Vec<_>>(),
+      vec![
+        vec![0],
+        vec![1, 0],
+        vec![1, 1],
+        vec![1],
+        vec![2],
+        vec![3],
+        vec![],
+      ]
+    );
+  }
+
+  #[test]
+  fn sexp_visitors() {
+    struct LeafCollector(Vec<String>);
+    impl Visitor<(), ()> for LeafCollector {
+      fn enter(&mut self, path: &[usize], node: &RawSexp) -> bool {
+        if let Sexp::Leaf(_, leaf) = node {
+          self.0.push(leaf.clone());
+        }
+        path != [1]
+      }
+    }
+    let mut sexp = visit_test_sexp();
+    let mut collector = LeafCollector(vec![]);
+    sexp.visit(&mut collector);
+    assert_eq!(collector.0, vec!["a", "d"]);
+
+    struct Renamer(Vec<Vec<usize>>);
+    impl VisitorMut<(), ()> for Renamer {
+      fn enter(&mut self, _path: &[usize], node: &mut RawSexp) -> bool {
+        if let Sexp::Leaf(_, leaf) = node {
+          if leaf == "b" {
+            *node = RawSexp::inner(vec![
+              RawSexp::leaf("x".to_string()),
+              RawSexp::leaf("y".to_string()),
+            ]);
+          }
+        }
+        true
+      }
+      fn exit_inner(&mut self, path: &[usize], _data: &mut ()) {
+        self.0.push(path.to_vec());
+      }
+    }
+    let mut renamer = Renamer(vec![]);
+    sexp.visit_mut(&mut renamer);
+    assert_eq!(sexp.to_string(), "(a ((x y) c) () d)");
+    assert_eq!(renamer.0, vec![vec![1, 0], vec![1], vec![2], vec![]]);
+  }
+
+  #[test]
+  fn sexp_fold_and_map() {
+    let sexp = visit_test_sexp();
+    assert_eq!(
+      sexp.fold(
+        |_, _| 0,
+        |_, depths| depths.into_iter().max().unwrap_or(0) + 1
+      ),
+      2
+    );
+    let mut count = 0;
+    let numbered = sexp.clone().map_inner_data(|_| {
+      count += 1;
+      count
+    });
+    assert_eq!(
+      numbered
+        .pre_order()
+        .filter_map(|(_, node)| match node {
+          Sexp::Inner(n, _) => Some(*n),
+          Sexp::Leaf(_, _) => None,
+        })
+        .collect::<Vec<_>>(),
+      vec![3, 1, 2]
+    );
+    let lengths = sexp
+      .clone()
+      .map_leaf_data(|_| 0)
+      .map_leaves(|leaf| leaf.to_uppercase());
+    assert_eq!(
+      lengths.fold(|_, leaf| leaf.to_string(), |_, leaves| leaves.join("")),
+      "ABCD"
+    );
+  }
+
+  #[test]
+  fn sexp_traversal_handles_deep_trees() {
+    let depth = 10_000;
+    let mut sexp = leaf("x".to_string());
+    for _ in 0..depth {
+      sexp = inner(vec![sexp]);
+    }
+    assert_eq!(sexp.pre_order().count(), depth + 1);
+    assert_eq!(sexp.post_order().last().unwrap().0, Vec::<usize>::new());
+    assert_eq!(sexp.fold(|_, _| 0, |_, depths| depths[0] + 1), depth);
+    let sexp = sexp.map_leaves(|_| "y".to_string());
+    assert_eq!(sexp.pre_order().last().unwrap().1, &leaf("y".to_string()));
+  }
+
+  #[test]
+  fn zipper_navigation() {
+    let mut zipper = visit_test_sexp().zipper();
+    assert!(zipper.up().is_none());
+    zipper
+      .down()
+      .unwrap()
+      .right()
+      .unwrap()
+      .down()
+      .unwrap()
+      .right()
+      .unwrap();
+    assert_eq!(zipper.path(), vec![1, 1]);
+    assert_eq!(zipper.focus(), &leaf("c".to_string()));
+    assert!(zipper.right().is_none());
+    zipper.up().unwrap().right().unwrap().right().unwrap();
+    assert_eq!(zipper.focus(), &leaf("d".to_string()));
+    zipper.left().unwrap();
+    assert_eq!(zipper.focus(), &inner(vec![]));
+    assert!(zipper.down().is_none());
+    zipper.root();
+    assert!(zipper.is_root());
+    assert_eq!(zipper.into_root(), visit_test_sexp());
+  }
+
+  #[test]
+  fn zipper_editing() {
+    let mut zipper = visit_test_sexp().zipper();
+    zipper.down_to(1).unwrap().down().unwrap();
+    assert_eq!(zipper.replace(leaf("x".to_string())), leaf("b".to_string()));
+    zipper
+      .insert_left(leaf("w".to_string()))
+      .unwrap()
+      .insert_right(leaf("y".to_string()))
+      .unwrap();
+    assert_eq!(zipper.path(), vec![1, 1]);
+    zipper.up().unwrap().right().unwrap();
+    zipper.insert_child(0, leaf("e".to_string())).unwrap();
+    assert!(zipper.insert_child(2, leaf("f".to_string())).is_none());
+    zipper.right().unwrap();
+    assert_eq!(zipper.remove(), Some(leaf("d".to_string())));
+    assert_eq!(zipper.focus(), &inner(vec![leaf("e".to_string())]));
+    zipper.down().unwrap();
+    assert_eq!(zipper.remove(), Some(leaf("e".to_string())));
+    assert_eq!(zipper.path(), vec![2]);
+    assert_eq!(zipper.into_root().to_string(), "(a (w x y c) ())");
+  }
+
+  #[test]
+  fn zipper_over_document_syntax_tree() {
+    let doc =
+      Document::from_text_with_syntax(sexp_graph(), "(a (b c))").unwrap();
+    let tree = doc.syntax_trees()[0].clone();
+    let mut zipper =
+      crate::zipper::Zipper::from_path(tree.clone(), &[1, 0]).unwrap();
+    assert_eq!(
+      zipper.focus(),
+      &DocumentSyntaxTree::Leaf(4..5, "b".to_string())
+    );
+    zipper.remove();
+    assert_eq!(zipper.focus().range(), &(6..7));
+    assert_eq!(
+      RawSexp::from(zipper.into_root()),
+      Parser::new(sexp_graph(), "(a (c))")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+    );
+    assert!(crate::zipper::Zipper::from_path(tree, &[1, 2]).is_err());
+  }
+
+  #[test]
+  fn syntax_graph_builder() {
+    let paren = StringTaggedEncloser::new("", "(", ")");
+    let string = StringTaggedEncloser::new("STRING", "\"", "\"");
+    let quote = StringTaggedOperator::new("QUOTE", "'", 0, 1);
+    let graph = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_whitespace_chars(standard_sexp_whitespace_chars())
+          .with_encloser(paren.clone(), "root")
+          .with_encloser(string.clone(), "string")
+          .with_operator(quote.clone(), "root")
+      })
+      .with_context("string", |context| context.with_escape_char("\\"))
+      .build()
+      .unwrap();
+    assert_eq!(graph.get_encloser_context_tag(&string), &"string");
+    assert_eq!(graph.get_context(&"string").escape_char(), Some("\\"));
+    let mut graph = graph;
+    assert_eq!(
+      graph.add_reader_macro(
+        &"island",
+        ReaderMacro::new("#_", |reader| Ok(vec![reader.leaf(0..0)]))
+      ),
+      Err(SyntaxGraphError::UndefinedContext {
+        id: "#_".to_string(),
+        context: "island",
+      })
+    );
+    assert_eq!(
+      Parser::new(graph, "'(a \"b c\")")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      Parser::new(string_sexp_graph(), "(QUOTE (a \"b c\"))")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string()
+    );
+    let errors = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_encloser(paren.clone(), "root")
+          .with_encloser(StringTaggedEncloser::new("SQUARE", "(", "]"), "root")
+          .with_operator(quote.clone(), "quoted")
+      })
+      .with_context("root", |context| context)
+      .with_context("island", |context| context)
+      .build()
+      .unwrap_err();
+    assert_eq!(
+      errors,
+      vec![
+        SyntaxGraphError::DuplicateContext("root"),
+        SyntaxGraphError::UndefinedContext {
+          id: "QUOTE".to_string(),
+          context: "quoted",
+        },
+        SyntaxGraphError::DuplicateMarker {
+          context: "root",
+          marker: "(".to_string(),
+        },
+        SyntaxGraphError::UnreachableContext("island"),
+      ]
+    );
+    assert_eq!(
+      StringTaggedSyntaxGraph::builder("root")
+        .with_context("root", |context| context)
+        .with_context("c", |context| context)
+        .with_context("a", |context| context)
+        .with_context("b", |context| context)
+        .build()
+        .unwrap_err(),
+      ["a", "b", "c"].map(SyntaxGraphError::UnreachableContext)
+    );
+  }
+
+  #[test]
+  fn owned_string_tagged_graph() {
+    let (root, string) = ("root".to_string(), "string".to_string());
+    let graph = OwnedStringTaggedSyntaxGraph::from_descriptions(
+      root.clone(),
+      vec![
+        (
+          root.clone(),
+          vec!["".to_string(), "STRING".to_string(), "PLUS".to_string()],
+          None,
+          standard_sexp_whitespace_chars(),
+        ),
+        (string.clone(), vec![], Some("\\".to_string()), vec![]),
+      ],
+      vec![
+        (
+          "".to_string(),
+          "(".to_string(),
+          ")".to_string(),
+          root.clone(),
+        ),
+        (
+          "STRING".to_string(),
+          "\"".to_string(),
+          "\"".to_string(),
+          string,
+        ),
+      ],
+      vec![("PLUS".to_string(), "+".to_string(), 1, 1, root)],
+    );
+    assert_eq!(graph.validate(), Ok(()));
+    let doc =
+      OwnedStringTaggedDocument::from_text_with_syntax(graph, "(a + \"b c\")")
+        .unwrap();
+    assert_eq!(doc.get_subtree_text(&[0, 0, 1]).unwrap(), "\"b c\"");
+    let borrowed = string_sexp_graph();
+    let kinded = borrowed
+      .to_builder()
+      .update_context("string", |context| context.with_kind(ContextKind::Text))
+      .build()
+      .unwrap();
+    let owned = OwnedStringTaggedSyntaxGraph::from(&kinded);
+    assert_eq!(
+      owned.get_context(&"string".into()).kind(),
+      ContextKind::Text
+    );
+    assert_eq!(owned.get_context(&"root".into()).kind(), ContextKind::Code);
+    assert_eq!(
+      Parser::new(owned, "(a \"b c\")")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      Parser::new(borrowed, "(a \"b c\")")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string()
+    );
+  }
+
+  #[test]
+  fn grammar_file_errors() {
+    let grammar = "; a tiny grammar\n\
+                   (root top)\n\
+                   (context top\n  (whitespace \" \")\n  \
+                   (members list quote unquote))\n\
+                   (context lonely)\n\
+                   (encloser list \"(\" \")\" top)\n\
+                   (operator quote \"'\" zero 1 top)\n\
+                   (encloser square \"[\" \"]\" top)\n";
+    let errors = read_grammar(grammar).unwrap_err();
+    assert_eq!(
+      errors[0],
+      GrammarError {
+        kind: GrammarErrorKind::MalformedForm("an argument count".to_string()),
+        range: Some(157..161),
+      }
+    );
+    assert_eq!(errors[0].row_and_col(grammar), Some((7, 20)));
+    assert!(errors.contains(&GrammarError {
+      kind: GrammarErrorKind::UnknownMember("unquote".to_string()),
+      range: Some(82..89),
+    }));
+    assert!(errors.contains(&GrammarError {
+      kind: GrammarErrorKind::Graph(SyntaxGraphError::UnreachableContext(
+        "lonely".into()
+      )),
+      range: Some(92..108),
+    }));
+    assert!(errors.contains(&GrammarError {
+      kind: GrammarErrorKind::UnusedDefinition("square".to_string()),
+      range: Some(169..198),
+    }));
+    let unclosed = "(root top)\n(context top\n  (members list)";
+    assert_eq!(
+      read_grammar(unclosed).unwrap_err(),
+      vec![GrammarError {
+        kind: GrammarErrorKind::Parse(ParseError::EndOfTextWithOpenEncloser(
+          "(".to_string()
+        )),
+        range: Some(11..unclosed.len()),
+      }]
+    );
+    let graph = read_grammar(
+      &grammar
+        .replace(" zero ", " 0 ")
+        .replace("(encloser square \"[\" \"]\" top)\n", "")
+        .replace(" unquote", "")
+        .replace("(context lonely)\n", ""),
+    )
+    .unwrap();
+    assert_eq!(
+      Parser::new(graph, "'(a b)")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      "(quote (list a b))"
+    );
+  }
+
+  #[test]
+  fn derived_syntax_graph() {
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, crate::SyntaxGraph)]
+    #[syntax_graph(
+      crate = "crate",
+      encloser = TemplateEncloser,
+      operator = TemplateOperator
+    )]
+    enum TemplateContext {
+      #[context(root)]
+      Text,
+      #[context(whitespace = [" "])]
+      Code,
+      #[context(extends = Code, escape = "\\")]
+      Quoted,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, crate::Encloser)]
+    #[encloser(crate = "crate", context = TemplateContext)]
+    enum TemplateEncloser {
+      #[encloser(open = "{{", close = "}}", context = Code, within = [Text])]
+      Splice,
+      #[encloser(
+        id = "",
+        open = "(",
+        close = ")",
+        context = Code,
+        within = [Code]
+      )]
+      Group,
+      #[encloser(
+        open = "'",
+        close = "'",
+        context = Quoted,
+        within = [Code]
+      )]
+      Quote,
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash, crate::Operator)]
+    #[operator(crate = "crate", context = TemplateContext)]
+    enum TemplateOperator {
+      #[operator(op = "|", left = 1, right = 1, context = Code, within = [Code])]
+      Pipe,
+    }
+
+    use crate::{Encloser, Operator};
+    assert_eq!(TemplateEncloser::Splice.id_str(), "Splice");
+    assert_eq!(TemplateEncloser::Group.closing_encloser_str(), ")");
+    assert_eq!(TemplateOperator::Pipe.left_args(), 1);
+    let graph = TemplateContext::syntax_graph().unwrap();
+    assert_eq!(
+      graph.get_context(&TemplateContext::Text).enclosers(),
+      &[TemplateEncloser::Splice]
+    );
+    assert_eq!(
+      graph.get_context(&TemplateContext::Code).operators(),
+      &[TemplateOperator::Pipe]
+    );
+    assert_eq!(
+      Parser::new(graph.clone(), "{{(a b) | c}}")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      "(Splice (Pipe (a b) c))"
+    );
+    let quoted = graph.get_context(&TemplateContext::Quoted);
+    assert_eq!(quoted.whitespace_chars(), &[" ".to_string()]);
+    assert_eq!(quoted.escape_char(), Some("\\"));
+    assert_eq!(
+      quoted.enclosers(),
+      graph.get_context(&TemplateContext::Code).enclosers()
+    );
+  }
+
+  #[test]
+  fn syntax_graph_analysis() {
+    let graph = StringTaggedSyntaxGraph::from_descriptions(
+      "root",
+      vec![
+        (
+          "root",
+          vec!["", "PIPE", "COMMENT", "STRING", "NOT", "MINUS"],
+          None,
+          standard_sexp_whitespace_chars(),
+        ),
+        ("comment", vec![], None, standard_sexp_whitespace_chars()),
+        (
+          "string",
+          vec!["INTERPOLATE"],
+          Some("\\".to_string()),
+          vec![],
+        ),
+      ],
+      vec![
+        ("", "(", ")", "root"),
+        ("PIPE", "|", "|", "root"),
+        ("COMMENT", ";", "\n", "comment"),
+        ("STRING", "\"", "\"", "string"),
+        ("INTERPOLATE", "\\(", ")", "root"),
+      ],
+      vec![("NOT", "not", 0, 1, "root"), ("MINUS", "-", 1, 1, "root")],
+    );
+    let ambiguity = |context, kind, example: &str| Ambiguity {
+      context,
+      kind,
+      example: example.to_string(),
+    };
+    assert_eq!(
+      graph.analyze(),
+      vec![
+        ambiguity(
+          "root",
+          AmbiguityKind::SwallowedCloser {
+            encloser: "COMMENT".to_string()
+          },
+          ";x\n"
+        ),
+        ambiguity(
+          "root",
+          AmbiguityKind::OperatorStartsLeaf {
+            operator: "NOT".to_string()
+          },
+          "noty"
+        ),
+        ambiguity(
+          "root",
+          AmbiguityKind::OperatorStartsLeaf {
+            operator: "MINUS".to_string()
+          },
+          "x-y"
+        ),
+        ambiguity(
+          "root",
+          AmbiguityKind::CloserIsOpener {
+            encloser: "PIPE".to_string(),
+            opener: "PIPE".to_string()
+          },
+          "||"
+        ),
+        ambiguity(
+          "string",
+          AmbiguityKind::EscapeCollision {
+            marker: "INTERPOLATE".to_string()
+          },
+          "\"\\(\""
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn syntax_graph_export() {
+    let graph = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_whitespace(" ")
+          .with_encloser(StringTaggedEncloser::new("", "(", ")"), "root")
+          .with_encloser(
+            StringTaggedEncloser::new("STRING", "\"", "\""),
+            "string",
+          )
+          .with_operator(StringTaggedOperator::new("QUOTE", "'", 0, 1), "root")
+      })
+      .with_context("string", |context| context.with_escape_char("\\"))
+      .build()
+      .unwrap();
+    assert_eq!(
+      graph.to_dot(),
+      [
+        "digraph syntax {",
+        "  \"root\" [shape=doublecircle];",
+        "  \"string\" [shape=circle];",
+        "  \"root\" -> \"root\" [label=\"( … )\"];",
+        "  \"root\" -> \"string\" [label=\"STRING \\\" … \\\"\"];",
+        "  \"root\" -> \"root\" [label=\"QUOTE ' (0, 1)\", style=dashed];",
+        "}\n",
+      ]
+      .join("\n")
+    );
+    assert_eq!(
+      graph.to_ebnf(),
+      [
+        "root = { root_form } ;",
+        "root_form = root_leaf",
+        "          | \"(\" , root , \")\"",
+        "          | \"\\\"\" , string , \"\\\"\"",
+        "          | \"'\" , root_form ;",
+        "root_leaf = ? any text up to whitespace (\" \"), a marker or a closer ? ;",
+        "",
+        "string = { string_form } ;",
+        "string_form = string_leaf ;",
+        "string_leaf = ? any text up to a marker or a closer, escaped by \"\\\\\" ? ;\n",
+      ]
+      .join("\n")
+    );
+  }
+
+  #[test]
+  fn syntax_graph_context_inheritance() {
+    let paren = StringTaggedEncloser::new("", "(", ")");
+    let string = StringTaggedEncloser::new("STRING", "\"", "\"");
+    let quote = StringTaggedOperator::new("QUOTE", "'", 0, 1);
+    let graph = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_whitespace_chars(standard_sexp_whitespace_chars())
+          .with_encloser(paren.clone(), "root")
+          .with_encloser(string.clone(), "string")
+          .with_operator(quote.clone(), "root")
+      })
+      .with_context_extending("string", "root", |context| {
+        context
+          .without_whitespace(" ")
+          .without_encloser(&string)
+          .without_operator(&quote)
+          .with_encloser(paren.clone(), "root")
+          .with_escape_char("\\")
+      })
+      .build()
+      .unwrap();
+    let string_context = graph.get_context(&"string");
+    assert_eq!(string_context.enclosers(), std::slice::from_ref(&paren));
+    assert!(string_context.operators().is_empty());
+    assert!(!string_context.is_whitespace(" "));
+    assert!(string_context.is_whitespace("\n"));
+    assert_eq!(
+      Parser::new(graph.clone(), "\"a 'b (c d)\"")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      "(STRING a 'b  (c d))"
+    );
+    let delta = graph
+      .to_builder()
+      .update_context("root", |context| context.without_operator(&quote))
+      .build()
+      .unwrap();
+    assert!(delta.get_context(&"root").operators().is_empty());
+    assert_eq!(delta.operators().count(), 0);
+    assert_eq!(
+      StringTaggedSyntaxGraph::builder("root")
+        .with_context_extending("root", "base", |context| {
+          context.with_encloser(paren.clone(), "root")
+        })
+        .build()
+        .unwrap_err(),
+      vec![SyntaxGraphError::UndefinedParentContext {
+        context: "root",
+        parent: "base",
+      }]
+    );
+    assert_eq!(
+      StringTaggedSyntaxGraph::builder("root")
+        .with_context("root", |context| {
+          context
+            .with_encloser(paren.clone(), "root")
+            .with_encloser(paren.clone(), "root")
+        })
+        .build()
+        .unwrap_err(),
+      vec![SyntaxGraphError::DuplicateMarker {
+        context: "root",
+        marker: "(".to_string(),
+      }]
+    );
+  }
+
+  #[test]
+  fn syntax_graph_merge() {
+    let paren = StringTaggedEncloser::new("", "(", ")");
+    let string = StringTaggedEncloser::new("STRING", "\"", "\"");
+    let square = StringTaggedEncloser::new("SQUARE", "[", "]");
+    let bar = StringTaggedEncloser::new("BAR", "[", "|");
+    let base = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_whitespace(" ")
+          .with_encloser(paren.clone(), "root")
+          .with_encloser(square.clone(), "root")
+      })
+      .build()
+      .unwrap();
+    let overlay = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context
+          .with_whitespace("\n")
+          .with_encloser(paren.clone(), "root")
+          .with_encloser(bar.clone(), "root")
+          .with_encloser(string.clone(), "string")
+      })
+      .with_context("string", |context| context.with_escape_char("\\"))
+      .build()
+      .unwrap();
+    let merged = base.merge(&overlay, ClashResolution::KeepBase).unwrap();
+    let root = merged.get_context(&"root");
+    assert_eq!(root.enclosers(), &[paren.clone(), square.clone(), string]);
+    assert_eq!(
+      root.whitespace_chars(),
+      &[" ".to_string(), "\n".to_string()]
+    );
+    assert_eq!(merged.get_context(&"string").escape_char(), Some("\\"));
+    assert_eq!(
+      Parser::new(merged, "(a\n[\"b c\"])")
+        .read_next_sexp()
+        .unwrap()
+        .unwrap()
+        .to_string(),
+      "(a (SQUARE (STRING b c)))"
+    );
+    let merged = base.merge(&overlay, ClashResolution::KeepOverlay).unwrap();
+    assert_eq!(
+      merged.get_context(&"root").enclosers()[..2],
+      [paren.clone(), bar.clone()]
+    );
+    assert_eq!(
+      base.merge(&overlay, ClashResolution::Reject).unwrap_err(),
+      vec![SyntaxGraphError::DuplicateMarker {
+        context: "root",
+        marker: "[".to_string()
+      }]
+    );
+    let retargeted = StringTaggedSyntaxGraph::builder("root")
+      .with_context("root", |context| {
+        context.with_encloser(paren.clone(), "list")
+      })
+      .with_context("list", |context| context.with_whitespace(" "))
+      .build()
+      .unwrap();
+    assert_eq!(
+      base
+        .merge(&retargeted, ClashResolution::KeepOverlay)
+        .unwrap()
+        .get_encloser_context_tag(&paren),
+      &"list"
+    );
+    assert_eq!(
+      base
+        .merge(&retargeted, ClashResolution::Reject)
+        .unwrap_err(),
+      vec![
+        SyntaxGraphError::ConflictingContexts {
+          id: "".to_string(),
+          first: "root",
+          second: "list"
+        },
+        SyntaxGraphError::UnreachableContext("list")
+      ]
+    );
+    let text_strings = overlay
+      .to_builder()
+      .update_context("string", |context| context.with_kind(ContextKind::Text))
+      .build()
+      .unwrap();
+    let kind_after = |resolution| {
+      overlay
+        .merge(&text_strings, resolution)
+        .map(|merged| merged.get_context(&"string").kind())
+    };
+    assert_eq!(kind_after(ClashResolution::KeepBase), Ok(ContextKind::Code));
+    assert_eq!(
+      kind_after(ClashResolution::KeepOverlay),
+      Ok(ContextKind::Text)
+    );
+    assert_eq!(
+      kind_after(ClashResolution::Reject),
+      Err(vec![SyntaxGraphError::ConflictingContextKinds {
+        context: "string"
+      }])
+    );
+  }
 }
@@ -0,0 +1,489 @@
+use std::{fmt::Debug, hash::Hash, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+  lex::Lexeme, parse::expand_reader_macro, reader_macro::ReaderMacro,
+  syntax::EncloserOrOperator, DocumentSyntaxTree, Encloser, Operator, Sexp,
+  SyntaxContext, SyntaxGraph,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MarkerKind<E: Encloser, O: Operator> {
+  Opener(E),
+  Closer(E),
+  Operator(O),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Marker<E: Encloser, O: Operator> {
+  pub range: Range<usize>,
+  pub kind: MarkerKind<E, O>,
+  pub partner: Option<Range<usize>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TokenKind<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  Marker(MarkerKind<E, O>),
+  Leaf(C),
+  Whitespace,
+  Discarded,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub range: Range<usize>,
+  pub kind: TokenKind<C, E, O>,
+}
+
+enum Frame<E: Encloser, O: Operator> {
+  Encloser {
+    encloser: E,
+    marker_index: usize,
+    forms: Vec<Range<usize>>,
+  },
+  Operator {
+    operator: O,
+    marker_index: usize,
+    start: usize,
+    remaining_args: usize,
+    arguments: Option<Range<usize>>,
+  },
+}
+
+pub(crate) struct ScannedText<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub(crate) markers: Vec<Marker<E, O>>,
+  pub(crate) tokens: Vec<Token<C, E, O>>,
+}
+
+pub(crate) struct MarkerScan<
+  'a,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  syntax_graph: &'a SyntaxGraph<C, E, O>,
+  text: &'a str,
+  frames: Vec<Frame<E, O>>,
+  top_level_forms: Vec<Range<usize>>,
+  markers: Vec<Marker<E, O>>,
+  tokens: Vec<Token<C, E, O>>,
+}
+
+impl<'a, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  MarkerScan<'a, C, E, O>
+{
+  pub(crate) fn new(
+    syntax_graph: &'a SyntaxGraph<C, E, O>,
+    text: &'a str,
+  ) -> Self {
+    Self {
+      syntax_graph,
+      text,
+      frames: vec![],
+      top_level_forms: vec![],
+      markers: vec![],
+      tokens: vec![],
+    }
+  }
+  pub(crate) fn switch_syntax_graph(
+    &mut self,
+    syntax_graph: &'a SyntaxGraph<C, E, O>,
+  ) {
+    self.syntax_graph = syntax_graph;
+  }
+  fn active_context_tag(&self) -> &'a C {
+    match self.frames.last() {
+      None => &self.syntax_graph.root,
+      Some(Frame::Encloser { encloser, .. }) => {
+        self.syntax_graph.get_encloser_context_tag(encloser)
+      }
+      Some(Frame::Operator { operator, .. }) => {
+        self.syntax_graph.get_operator_context_tag(operator)
+      }
+    }
+  }
+  fn active_context(&self) -> &'a SyntaxContext<E, O> {
+    self.syntax_graph.get_context(self.active_context_tag())
+  }
+  fn awaited_closer(&self) -> Option<&E> {
+    self.frames.iter().rev().find_map(|frame| match frame {
+      Frame::Encloser { encloser, .. } => Some(encloser),
+      Frame::Operator { .. } => None,
+    })
+  }
+  fn sibling_forms(&mut self) -> &mut Vec<Range<usize>> {
+    match self.frames.last_mut() {
+      Some(Frame::Encloser { forms, .. }) => forms,
+      _ => &mut self.top_level_forms,
+    }
+  }
+  fn push_token(&mut self, range: Range<usize>, kind: TokenKind<C, E, O>) {
+    if let Some(previous) = self.tokens.last_mut() {
+      if previous.range.end == range.start
+        && previous.kind == kind
+        && matches!(kind, TokenKind::Whitespace | TokenKind::Discarded)
+      {
+        previous.range.end = range.end;
+        return;
+      }
+    }
+    self.tokens.push(Token { range, kind });
+  }
+  fn push_marker(&mut self, marker: Marker<E, O>) -> usize {
+    self
+      .push_token(marker.range.clone(), TokenKind::Marker(marker.kind.clone()));
+    self.markers.push(marker);
+    self.markers.len() - 1
+  }
+  fn push_gap(&mut self, gap: Range<usize>, context_tag: &C) {
+    if gap.start >= gap.end {
+      return;
+    }
+    let context = self.syntax_graph.get_context(context_tag);
+    for (offset, grapheme) in self.text[gap.clone()].grapheme_indices(true) {
+      let start = gap.start + offset;
+      let kind = if context.is_whitespace(grapheme) {
+        TokenKind::Whitespace
+      } else {
+        TokenKind::Discarded
+      };
+      self.push_token(start..start + grapheme.len(), kind);
+    }
+  }
+  fn push_children(
+    &mut self,
+    children: &[DocumentSyntaxTree<E, O>],
+    span: Range<usize>,
+    context_tag: &C,
+  ) {
+    let mut position = span.start;
+    for child in children {
+      self.push_gap(position..child.range().start, context_tag);
+      self.push_tree(child, context_tag);
+      position = position.max(child.range().end);
+    }
+    self.push_gap(position..span.end, context_tag);
+  }
+  fn push_tree(&mut self, tree: &DocumentSyntaxTree<E, O>, context_tag: &C) {
+    match tree {
+      Sexp::Leaf(range, _) => {
+        self.push_token(range.clone(), TokenKind::Leaf(context_tag.clone()))
+      }
+      Sexp::Inner(
+        (range, EncloserOrOperator::Encloser(encloser)),
+        children,
+      ) => {
+        let opener =
+          range.start..range.start + encloser.opening_encloser_str().len();
+        let closer =
+          range.end - encloser.closing_encloser_str().len()..range.end;
+        self.push_marker(Marker {
+          range: opener.clone(),
+          kind: MarkerKind::Opener(encloser.clone()),
+          partner: Some(closer.clone()),
+        });
+        self.push_children(
+          children,
+          opener.end..closer.start,
+          self.syntax_graph.get_encloser_context_tag(encloser),
+        );
+        self.push_marker(Marker {
+          range: closer,
+          kind: MarkerKind::Closer(encloser.clone()),
+          partner: Some(opener),
+        });
+      }
+      Sexp::Inner(
+        (range, EncloserOrOperator::Operator(operator)),
+        children,
+      ) => {
+        let (left_args, right_args) =
+          children.split_at(operator.left_args().min(children.len()));
+        let marker_search_start = left_args
+          .last()
+          .map(|arg| arg.range().end)
+          .unwrap_or(range.start);
+        let marker_start = marker_search_start
+          + self.text[marker_search_start..]
+            .find(operator.op_str())
+            .unwrap_or(0);
+        let marker = marker_start..marker_start + operator.op_str().len();
+        self.push_children(left_args, range.start..marker.start, context_tag);
+        self.push_marker(Marker {
+          range: marker.clone(),
+          kind: MarkerKind::Operator(operator.clone()),
+          partner: children
+            .first()
+            .zip(children.last())
+            .map(|(first, last)| first.range().start..last.range().end),
+        });
+        self.push_children(
+          right_args,
+          marker.end..range.end.max(marker.end),
+          self.syntax_graph.get_operator_context_tag(operator),
+        );
+      }
+    }
+  }
+  pub(crate) fn push_trees(
+    &mut self,
+    trees: &[DocumentSyntaxTree<E, O>],
+    span: Range<usize>,
+  ) {
+    let root = &self.syntax_graph.root;
+    self.push_children(trees, span, root);
+    self
+      .top_level_forms
+      .extend(trees.iter().map(|tree| tree.range().clone()));
+  }
+  fn finish_operator(&mut self) -> Option<Range<usize>> {
+    if let Some(Frame::Operator {
+      marker_index,
+      start,
+      arguments,
+      ..
+    }) = self.frames.pop()
+    {
+      let end = arguments
+        .as_ref()
+        .map(|arguments| arguments.end)
+        .unwrap_or(self.markers[marker_index].range.end);
+      self.markers[marker_index].partner = arguments;
+      Some(start..end)
+    } else {
+      None
+    }
+  }
+  fn complete_form(&mut self, mut form: Range<usize>) {
+    loop {
+      match self.frames.last_mut() {
+        Some(Frame::Operator {
+          remaining_args,
+          arguments,
+          ..
+        }) => {
+          *arguments = Some(
+            arguments
+              .as_ref()
+              .map(|arguments| arguments.start)
+              .unwrap_or(form.start)..form.end,
+          );
+          *remaining_args = remaining_args.saturating_sub(1);
+          if *remaining_args == 0 {
+            form = self.finish_operator().unwrap();
+          } else {
+            return;
+          }
+        }
+        Some(Frame::Encloser { forms, .. }) => {
+          forms.push(form);
+          return;
+        }
+        None => {
+          self.top_level_forms.push(form);
+          return;
+        }
+      }
+    }
+  }
+  fn close_encloser(&mut self, closer: Range<usize>) {
+    while let Some(Frame::Operator { .. }) = self.frames.last() {
+      let form = self.finish_operator().unwrap();
+      self.sibling_forms().push(form);
+    }
+    if let Some(Frame::Encloser { marker_index, .. }) = self.frames.pop() {
+      let opener = self.markers[marker_index].range.clone();
+      self.markers[marker_index].partner = Some(closer.clone());
+      if let MarkerKind::Opener(encloser) =
+        self.markers[marker_index].kind.clone()
+      {
+        self.push_marker(Marker {
+          range: closer.clone(),
+          kind: MarkerKind::Closer(encloser),
+          partner: Some(opener.clone()),
+        });
+      }
+      self.complete_form(opener.start..closer.end);
+    }
+  }
+  fn open_encloser(&mut self, encloser: E, opener: Range<usize>) {
+    let marker_index = self.push_marker(Marker {
+      range: opener,
+      kind: MarkerKind::Opener(encloser.clone()),
+      partner: None,
+    });
+    self.frames.push(Frame::Encloser {
+      encloser,
+      marker_index,
+      forms: vec![],
+    });
+  }
+  fn open_operator(&mut self, operator: O, marker: Range<usize>) {
+    let siblings = self.sibling_forms();
+    let left_args =
+      siblings.split_off(siblings.len().saturating_sub(operator.left_args()));
+    let arguments = left_args
+      .first()
+      .zip(left_args.last())
+      .map(|(first, last)| first.start..last.end);
+    let right_args = operator.right_args();
+    let start = arguments
+      .as_ref()
+      .map(|arguments| arguments.start)
+      .unwrap_or(marker.start);
+    let marker_index = self.push_marker(Marker {
+      range: marker,
+      kind: MarkerKind::Operator(operator.clone()),
+      partner: None,
+    });
+    self.frames.push(Frame::Operator {
+      operator,
+      marker_index,
+      start,
+      remaining_args: right_args,
+      arguments,
+    });
+    if right_args == 0 {
+      let form = self.finish_operator().unwrap();
+      self.complete_form(form);
+    }
+  }
+  fn finish_terminal(&mut self, terminal: Range<usize>, context_tag: &C) {
+    self.push_token(terminal.clone(), TokenKind::Leaf(context_tag.clone()));
+    self.complete_form(terminal);
+  }
+  fn expand_reader_macro(
+    &mut self,
+    reader_macro: &ReaderMacro<E, O>,
+    index: usize,
+  ) -> Option<usize> {
+    let context_tag = self.active_context_tag();
+    let (trees, end) = expand_reader_macro(
+      self.syntax_graph,
+      context_tag,
+      self.text,
+      reader_macro,
+      index,
+    )
+    .ok()?;
+    let mut position = index;
+    for tree in &trees {
+      self.push_gap(position..tree.range().start, context_tag);
+      self.push_tree(tree, context_tag);
+      self.complete_form(tree.range().clone());
+      position = position.max(tree.range().end);
+    }
+    self.push_gap(position..end, context_tag);
+    Some(end)
+  }
+  pub(crate) fn scan(&mut self, span: Range<usize>) {
+    let mut terminal_start: Option<(usize, &C)> = None;
+    let mut escaped = false;
+    let mut skip_until = span.start;
+    for (offset, character) in self.text[span.clone()].grapheme_indices(true) {
+      let index = span.start + offset;
+      if index < skip_until {
+        continue;
+      }
+      macro_rules! finish_terminal {
+        () => {
+          if let Some((start, context_tag)) = terminal_start.take() {
+            self.finish_terminal(start..index, context_tag);
+          }
+        };
+      }
+      if escaped {
+        escaped = false;
+        continue;
+      }
+      let awaited_closer = self.awaited_closer().cloned();
+      match Lexeme::lex(
+        self.active_context(),
+        awaited_closer
+          .as_ref()
+          .map(|encloser| encloser.closing_encloser_str()),
+        &self.text[index..],
+        character,
+      ) {
+        Lexeme::Escape => {
+          escaped = true;
+          terminal_start.get_or_insert((index, self.active_context_tag()));
+        }
+        Lexeme::Whitespace => {
+          finish_terminal!();
+          self
+            .push_token(index..index + character.len(), TokenKind::Whitespace);
+        }
+        Lexeme::AwaitedCloser => {
+          finish_terminal!();
+          skip_until =
+            index + awaited_closer.unwrap().closing_encloser_str().len();
+          self.close_encloser(index..skip_until);
+        }
+        Lexeme::ReaderMacro(reader_macro) => {
+          finish_terminal!();
+          let marker_end = index + reader_macro.marker().len();
+          match self.expand_reader_macro(reader_macro, index) {
+            Some(end) => skip_until = end.max(marker_end),
+            None => {
+              terminal_start = Some((index, self.active_context_tag()));
+              skip_until = marker_end;
+            }
+          }
+        }
+        Lexeme::Opener(encloser) => {
+          finish_terminal!();
+          skip_until = index + encloser.opening_encloser_str().len();
+          self.open_encloser(encloser.clone(), index..skip_until);
+        }
+        Lexeme::Operator(operator) => {
+          finish_terminal!();
+          skip_until = index + operator.op_str().len();
+          self.open_operator(operator.clone(), index..skip_until);
+        }
+        Lexeme::UnexpectedCloser(encloser) => {
+          finish_terminal!();
+          skip_until = index + encloser.closing_encloser_str().len();
+          self.push_marker(Marker {
+            range: index..skip_until,
+            kind: MarkerKind::Closer(encloser.clone()),
+            partner: None,
+          });
+        }
+        Lexeme::Terminal => {
+          terminal_start.get_or_insert((index, self.active_context_tag()));
+        }
+      }
+    }
+    if let Some((start, context_tag)) = terminal_start {
+      self.finish_terminal(start..span.end, context_tag);
+    }
+    while let Some(frame) = self.frames.last() {
+      if let Frame::Operator { .. } = frame {
+        let form = self.finish_operator().unwrap();
+        self.sibling_forms().push(form);
+      } else {
+        self.frames.pop();
+      }
+    }
+  }
+  pub(crate) fn finish(self) -> ScannedText<C, E, O> {
+    ScannedText {
+      markers: self.markers,
+      tokens: self.tokens,
+    }
+  }
+}
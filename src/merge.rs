@@ -0,0 +1,407 @@
+use std::{fmt::Debug, hash::Hash, ops::Range};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+  diff::{longest_common_subsequence, similar},
+  document::Document,
+  syntax::EncloserOrOperator,
+  DocumentSyntaxTree, Encloser, Operator, Sexp, SyntaxGraph, SyntaxTree,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeConflict {
+  pub range: Range<usize>,
+  pub base: String,
+  pub ours: String,
+  pub theirs: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeResult {
+  pub text: String,
+  pub conflicts: Vec<MergeConflict>,
+}
+
+impl MergeResult {
+  pub fn is_clean(&self) -> bool {
+    self.conflicts.is_empty()
+  }
+}
+
+fn corresponds<E: Encloser, O: Operator>(
+  base: &SyntaxTree<E, O>,
+  other: &SyntaxTree<E, O>,
+) -> bool {
+  match (base, other) {
+    (
+      Sexp::Inner(base_tag @ EncloserOrOperator::Operator(_), _),
+      Sexp::Inner(other_tag, _),
+    ) => base_tag == other_tag,
+    _ => similar(base, other),
+  }
+}
+
+fn correspondence<E: Encloser, O: Operator>(
+  base: &[SyntaxTree<E, O>],
+  other: &[SyntaxTree<E, O>],
+) -> Vec<Option<usize>> {
+  let mut corresponding = vec![None; base.len()];
+  let (mut base_start, mut other_start) = (0, 0);
+  for (base_index, other_index) in longest_common_subsequence(base, other)
+    .into_iter()
+    .chain(std::iter::once((base.len(), other.len())))
+  {
+    let mut other_cursor = other_start;
+    for i in base_start..base_index {
+      if let Some(j) =
+        (other_cursor..other_index).find(|&j| corresponds(&base[i], &other[j]))
+      {
+        corresponding[i] = Some(j);
+        other_cursor = j + 1;
+      }
+    }
+    if base_index < base.len() {
+      corresponding[base_index] = Some(other_index);
+    }
+    base_start = base_index + 1;
+    other_start = other_index + 1;
+  }
+  corresponding
+}
+
+fn pick<'s>(base: &'s str, ours: &'s str, theirs: &'s str) -> &'s str {
+  if ours == base {
+    theirs
+  } else {
+    ours
+  }
+}
+
+struct MergeSide<'d, E: Encloser, O: Operator> {
+  text: &'d str,
+  nodes: &'d [DocumentSyntaxTree<E, O>],
+  trees: Vec<SyntaxTree<E, O>>,
+  start: usize,
+  end: usize,
+}
+
+impl<'d, E: Encloser, O: Operator> MergeSide<'d, E, O> {
+  fn new(
+    text: &'d str,
+    nodes: &'d [DocumentSyntaxTree<E, O>],
+    start: usize,
+    end: usize,
+  ) -> Self {
+    Self {
+      text,
+      nodes,
+      trees: nodes.iter().cloned().map(SyntaxTree::from).collect(),
+      start,
+      end,
+    }
+  }
+  fn from_document<C: Clone + Debug + PartialEq + Eq + Hash>(
+    document: &'d Document<'_, C, E, O>,
+  ) -> Self {
+    Self::new(
+      &document.text,
+      document.syntax_trees(),
+      0,
+      document.text.len(),
+    )
+  }
+  fn children(&self, index: usize) -> Self {
+    let Sexp::Inner((range, encloser_or_operator), children) =
+      &self.nodes[index]
+    else {
+      unreachable!()
+    };
+    let (mut start, mut end) = (range.start, range.end);
+    if let EncloserOrOperator::Encloser(encloser) = encloser_or_operator {
+      let node_text = &self.text[range.clone()];
+      if node_text.starts_with(encloser.opening_encloser_str()) {
+        start += encloser.opening_encloser_str().len();
+      }
+      if node_text[start - range.start..]
+        .ends_with(encloser.closing_encloser_str())
+      {
+        end -= encloser.closing_encloser_str().len();
+      }
+    }
+    Self::new(self.text, children, start, end)
+  }
+  fn leading(&self, index: usize) -> &'d str {
+    let previous_end = match index {
+      0 => self.start,
+      _ => self.nodes[index - 1].range().end,
+    };
+    &self.text[previous_end..self.nodes[index].range().start]
+  }
+  fn trailing(&self) -> &'d str {
+    let last_end = self
+      .nodes
+      .last()
+      .map_or(self.start, |last| last.range().end);
+    &self.text[last_end..self.end]
+  }
+  fn prefix(&self, index: usize) -> &'d str {
+    &self.text[self.nodes[index].range().start..self.children(index).start]
+  }
+  fn suffix(&self, index: usize) -> &'d str {
+    &self.text[self.children(index).end..self.nodes[index].range().end]
+  }
+  fn node_text(&self, index: usize) -> &'d str {
+    &self.text[self.nodes[index].range().clone()]
+  }
+  fn region_text(&self, region: Range<usize>) -> String {
+    region
+      .map(|index| format!("{}{}", self.leading(index), self.node_text(index)))
+      .collect()
+  }
+}
+
+struct Merger<
+  'g,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  syntax_graph: &'g SyntaxGraph<C, E, O>,
+  context_tag: &'g C,
+  text: String,
+  conflicts: Vec<MergeConflict>,
+}
+
+impl<'g, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  Merger<'g, C, E, O>
+{
+  fn new(syntax_graph: &'g SyntaxGraph<C, E, O>, context_tag: &'g C) -> Self {
+    Self {
+      syntax_graph,
+      context_tag,
+      text: String::new(),
+      conflicts: vec![],
+    }
+  }
+  fn conflict_syntax(&self) -> Option<(&'g E, &'g str, &'g str)> {
+    let context = self.syntax_graph.get_context(self.context_tag);
+    let whitespace = |preferred: &str| {
+      context
+        .whitespace_chars()
+        .iter()
+        .find(|whitespace| *whitespace == preferred)
+        .or(context.whitespace_chars().first())
+    };
+    let encloser = context.enclosers().iter().find(|encloser| {
+      self.syntax_graph.get_encloser_context_tag(encloser) == self.context_tag
+    })?;
+    Some((encloser, whitespace(" ")?, whitespace("\n")?))
+  }
+  fn conflict(&mut self, base: &str, ours: &str, theirs: &str) {
+    let (base, ours, theirs) =
+      (base.trim_start(), ours.trim_start(), theirs.trim_start());
+    let start;
+    if let Some((encloser, space, newline)) = self.conflict_syntax() {
+      let context = self.syntax_graph.get_context(self.context_tag);
+      if self
+        .text
+        .graphemes(true)
+        .next_back()
+        .is_some_and(|grapheme| !context.is_whitespace(grapheme))
+      {
+        self.text.push_str(space);
+      }
+      start = self.text.len();
+      let (opener, closer) = (
+        encloser.opening_encloser_str(),
+        encloser.closing_encloser_str(),
+      );
+      let side = |tag: &str, text: &str| match text {
+        "" => format!("{opener}{tag}{closer}"),
+        _ => format!("{opener}{tag}{space}{text}{closer}"),
+      };
+      self.text.push_str(&format!(
+        "{opener}merge-conflict{newline}{}{newline}{}{newline}{}{closer}",
+        side("ours", ours),
+        side("base", base),
+        side("theirs", theirs)
+      ));
+    } else {
+      if !self.text.is_empty() && !self.text.ends_with('\n') {
+        self.text.push('\n');
+      }
+      start = self.text.len();
+      self.text.push_str(&format!(
+        "<<<<<<< ours\n{}\n||||||| base\n{}\n=======\n{}\n\
+         >>>>>>> theirs\n",
+        ours.trim_end(),
+        base.trim_end(),
+        theirs.trim_end()
+      ));
+    }
+    self.conflicts.push(MergeConflict {
+      range: start..self.text.len(),
+      base: base.to_string(),
+      ours: ours.to_string(),
+      theirs: theirs.to_string(),
+    });
+  }
+  fn merge_region(
+    &mut self,
+    (base, base_region): (&MergeSide<E, O>, Range<usize>),
+    (ours, ours_region): (&MergeSide<E, O>, Range<usize>),
+    (theirs, theirs_region): (&MergeSide<E, O>, Range<usize>),
+  ) {
+    let base_trees = &base.trees[base_region.clone()];
+    let ours_trees = &ours.trees[ours_region.clone()];
+    let theirs_trees = &theirs.trees[theirs_region.clone()];
+    if ours_trees == base_trees {
+      self.text.push_str(&theirs.region_text(theirs_region));
+    } else if theirs_trees == base_trees || ours_trees == theirs_trees {
+      self.text.push_str(&ours.region_text(ours_region));
+    } else {
+      self.conflict(
+        &base.region_text(base_region),
+        &ours.region_text(ours_region),
+        &theirs.region_text(theirs_region),
+      );
+    }
+  }
+  fn merge_nodes(
+    &mut self,
+    (base, b): (&MergeSide<E, O>, usize),
+    (ours, o): (&MergeSide<E, O>, usize),
+    (theirs, t): (&MergeSide<E, O>, usize),
+  ) {
+    let (base_tree, ours_tree, theirs_tree) =
+      (&base.trees[b], &ours.trees[o], &theirs.trees[t]);
+    if ours_tree == theirs_tree {
+      self.text.push_str(pick(
+        base.node_text(b),
+        ours.node_text(o),
+        theirs.node_text(t),
+      ));
+    } else if ours_tree == base_tree {
+      self.text.push_str(theirs.node_text(t));
+    } else if theirs_tree == base_tree {
+      self.text.push_str(ours.node_text(o));
+    } else if let (
+      Sexp::Inner(base_tag, _),
+      Sexp::Inner(ours_tag, _),
+      Sexp::Inner(theirs_tag, _),
+    ) = (base_tree, ours_tree, theirs_tree)
+    {
+      if base_tag == ours_tag && base_tag == theirs_tag {
+        let mut children = Merger::new(
+          self.syntax_graph,
+          match base_tag {
+            EncloserOrOperator::Encloser(encloser) => {
+              self.syntax_graph.get_encloser_context_tag(encloser)
+            }
+            EncloserOrOperator::Operator(operator) => {
+              self.syntax_graph.get_operator_context_tag(operator)
+            }
+          },
+        );
+        children.merge_sequences(
+          &base.children(b),
+          &ours.children(o),
+          &theirs.children(t),
+        );
+        if !children.conflicts.is_empty()
+          && children.conflict_syntax().is_none()
+        {
+          self.conflict(
+            base.node_text(b),
+            ours.node_text(o),
+            theirs.node_text(t),
+          );
+          return;
+        }
+        self.text.push_str(pick(
+          base.prefix(b),
+          ours.prefix(o),
+          theirs.prefix(t),
+        ));
+        let offset = self.text.len();
+        self.text.push_str(&children.text);
+        self.conflicts.extend(children.conflicts.into_iter().map(
+          |mut conflict| {
+            conflict.range =
+              conflict.range.start + offset..conflict.range.end + offset;
+            conflict
+          },
+        ));
+        self.text.push_str(pick(
+          base.suffix(b),
+          ours.suffix(o),
+          theirs.suffix(t),
+        ));
+      } else {
+        self.conflict(
+          base.node_text(b),
+          ours.node_text(o),
+          theirs.node_text(t),
+        );
+      }
+    } else {
+      self.conflict(base.node_text(b), ours.node_text(o), theirs.node_text(t));
+    }
+  }
+  fn merge_sequences(
+    &mut self,
+    base: &MergeSide<E, O>,
+    ours: &MergeSide<E, O>,
+    theirs: &MergeSide<E, O>,
+  ) {
+    let ours_correspondence = correspondence(&base.trees, &ours.trees);
+    let theirs_correspondence = correspondence(&base.trees, &theirs.trees);
+    let anchors = (0..base.trees.len()).filter_map(|i| {
+      Some((i, ours_correspondence[i]?, theirs_correspondence[i]?))
+    });
+    let (mut b, mut o, mut t) = (0, 0, 0);
+    for (i, j, k) in anchors {
+      self.merge_region((base, b..i), (ours, o..j), (theirs, t..k));
+      self.text.push_str(pick(
+        base.leading(i),
+        ours.leading(j),
+        theirs.leading(k),
+      ));
+      self.merge_nodes((base, i), (ours, j), (theirs, k));
+      (b, o, t) = (i + 1, j + 1, k + 1);
+    }
+    self.merge_region(
+      (base, b..base.trees.len()),
+      (ours, o..ours.trees.len()),
+      (theirs, t..theirs.trees.len()),
+    );
+    self.text.push_str(pick(
+      base.trailing(),
+      ours.trailing(),
+      theirs.trailing(),
+    ));
+  }
+}
+
+pub fn merge_documents<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  base: &Document<'_, C, E, O>,
+  ours: &Document<'_, C, E, O>,
+  theirs: &Document<'_, C, E, O>,
+) -> MergeResult {
+  let syntax_graph = base.syntax_graph();
+  let mut merger = Merger::new(syntax_graph, syntax_graph.root());
+  merger.merge_sequences(
+    &MergeSide::from_document(base),
+    &MergeSide::from_document(ours),
+    &MergeSide::from_document(theirs),
+  );
+  MergeResult {
+    text: merger.text,
+    conflicts: merger.conflicts,
+  }
+}
@@ -0,0 +1,111 @@
+use std::{fmt::Debug, ops::Range};
+
+use crate::{
+  syntax::EncloserOrOperator, DocumentSyntaxTree, Encloser, Operator, Sexp,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineRule<E: Encloser, K: Clone + Debug> {
+  pub encloser: E,
+  pub head: String,
+  pub kind: K,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutlineSymbol<K: Clone + Debug> {
+  pub kind: K,
+  pub name: String,
+  pub range: Range<usize>,
+  pub name_range: Range<usize>,
+  pub children: Vec<OutlineSymbol<K>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Outline<E: Encloser, K: Clone + Debug> {
+  rules: Vec<OutlineRule<E, K>>,
+}
+
+impl<E: Encloser, K: Clone + Debug> Default for Outline<E, K> {
+  fn default() -> Self {
+    Self { rules: vec![] }
+  }
+}
+
+fn symbol_name<E: Encloser, O: Operator>(
+  tree: &DocumentSyntaxTree<E, O>,
+) -> Option<(&str, &Range<usize>)> {
+  match tree {
+    Sexp::Leaf(range, name) => Some((name, range)),
+    Sexp::Inner((_, EncloserOrOperator::Operator(_)), args) => {
+      args.last().and_then(symbol_name)
+    }
+    Sexp::Inner((_, EncloserOrOperator::Encloser(_)), _) => None,
+  }
+}
+
+impl<E: Encloser, K: Clone + Debug> Outline<E, K> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+  pub fn with_rule(
+    mut self,
+    encloser: E,
+    head: impl Into<String>,
+    kind: K,
+  ) -> Self {
+    self.rules.push(OutlineRule {
+      encloser,
+      head: head.into(),
+      kind,
+    });
+    self
+  }
+  pub fn rules(&self) -> &[OutlineRule<E, K>] {
+    &self.rules
+  }
+  fn match_symbol<O: Operator>(
+    &self,
+    tree: &DocumentSyntaxTree<E, O>,
+  ) -> Option<OutlineSymbol<K>> {
+    let Sexp::Inner((range, EncloserOrOperator::Encloser(encloser)), children) =
+      tree
+    else {
+      return None;
+    };
+    let Some(Sexp::Leaf(_, head)) = children.first() else {
+      return None;
+    };
+    let rule = self
+      .rules
+      .iter()
+      .find(|rule| &rule.encloser == encloser && &rule.head == head)?;
+    let (name, name_range) = children.get(1).and_then(symbol_name)?;
+    Some(OutlineSymbol {
+      kind: rule.kind.clone(),
+      name: name.to_string(),
+      range: range.clone(),
+      name_range: name_range.clone(),
+      children: vec![],
+    })
+  }
+  pub fn extract<O: Operator>(
+    &self,
+    trees: &[DocumentSyntaxTree<E, O>],
+  ) -> Vec<OutlineSymbol<K>> {
+    trees
+      .iter()
+      .flat_map(|tree| {
+        let children = match tree {
+          Sexp::Leaf(_, _) => vec![],
+          Sexp::Inner(_, children) => self.extract(children),
+        };
+        if let Some(mut symbol) = self.match_symbol(tree) {
+          symbol.children = children;
+          vec![symbol]
+        } else {
+          children
+        }
+      })
+      .collect()
+  }
+}
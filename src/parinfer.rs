@@ -0,0 +1,450 @@
+use std::{
+  fmt::{Debug, Display},
+  hash::Hash,
+  ops::Range,
+};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+  document::TextEdit, lex::Lexeme, parse::expand_reader_macro,
+  reader_macro::ReaderMacro, syntax::ContextKind, Encloser, Operator,
+  SyntaxContext, SyntaxGraph,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParinferMode {
+  Indent,
+  Paren,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParinferError {
+  UnmatchedCloser(Range<usize>),
+  UnclosedEncloser(Range<usize>),
+}
+
+impl Display for ParinferError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use ParinferError::*;
+    match self {
+      UnmatchedCloser(range) => {
+        write!(f, "unmatched closer at {}..{}", range.start, range.end)
+      }
+      UnclosedEncloser(range) => {
+        write!(f, "unclosed encloser at {}..{}", range.start, range.end)
+      }
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParinferResult {
+  pub text: String,
+  pub edits: Vec<TextEdit>,
+  pub cursor: Option<usize>,
+}
+
+#[derive(Clone, Debug)]
+struct Frame<E: Encloser> {
+  encloser: E,
+  opener: Range<usize>,
+  col: usize,
+  kind: ContextKind,
+}
+
+struct PendingLine {
+  insertion: usize,
+  removal_end: usize,
+  closers: String,
+}
+
+struct Parinfer<
+  'a,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  syntax_graph: &'a SyntaxGraph<C, E, O>,
+  syntax_regions: Vec<(usize, &'a SyntaxGraph<C, E, O>)>,
+  text: &'a str,
+  mode: ParinferMode,
+  cursor: Option<usize>,
+  stack: Vec<Frame<E>>,
+  edits: Vec<TextEdit>,
+  line_start: usize,
+  col_shift: isize,
+  code_end: Option<usize>,
+  trail: Vec<(Frame<E>, Range<usize>)>,
+  pending_line: Option<PendingLine>,
+  previous_trail_min_col: Option<usize>,
+}
+
+impl<'a, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  Parinfer<'a, C, E, O>
+{
+  fn new(
+    syntax_regions: Vec<(usize, &'a SyntaxGraph<C, E, O>)>,
+    text: &'a str,
+    mode: ParinferMode,
+    cursor: Option<usize>,
+  ) -> Self {
+    Self {
+      syntax_graph: syntax_regions[0].1,
+      syntax_regions,
+      text,
+      mode,
+      cursor,
+      stack: vec![],
+      edits: vec![],
+      line_start: 0,
+      col_shift: 0,
+      code_end: None,
+      trail: vec![],
+      pending_line: None,
+      previous_trail_min_col: None,
+    }
+  }
+  fn enter_region(&mut self, index: usize) {
+    if self.stack.is_empty() {
+      let region_index = self
+        .syntax_regions
+        .partition_point(|&(start, _)| start <= index);
+      self.syntax_graph = self.syntax_regions[region_index - 1].1;
+    }
+  }
+  fn active_context_tag(&self) -> &'a C {
+    self
+      .stack
+      .last()
+      .map(|frame| self.syntax_graph.get_encloser_context_tag(&frame.encloser))
+      .unwrap_or(&self.syntax_graph.root)
+  }
+  fn active_context(&self) -> &'a SyntaxContext<E, O> {
+    self.syntax_graph.get_context(self.active_context_tag())
+  }
+  fn code(&mut self, end: usize) {
+    if !self
+      .stack
+      .last()
+      .is_some_and(|frame| frame.kind == ContextKind::Comment)
+    {
+      self.code_end = Some(end);
+      self.trail.clear();
+    }
+  }
+  fn open(&mut self, encloser: E, opener: Range<usize>) {
+    let kind = self
+      .syntax_graph
+      .get_context(self.syntax_graph.get_encloser_context_tag(&encloser))
+      .kind();
+    if kind != ContextKind::Comment {
+      self.code(opener.end);
+    }
+    self.stack.push(Frame {
+      encloser,
+      col: (opener.start as isize - self.line_start as isize + self.col_shift)
+        as usize,
+      opener,
+      kind,
+    });
+  }
+  fn close(&mut self, closer: Range<usize>) {
+    let frame = self.stack.pop().unwrap();
+    match frame.kind {
+      ContextKind::Code => self.trail.push((frame, closer)),
+      ContextKind::Text => self.code(closer.end),
+      ContextKind::Comment => {}
+    }
+  }
+  fn reader_macro_end(
+    &self,
+    reader_macro: &ReaderMacro<E, O>,
+    index: usize,
+  ) -> usize {
+    let marker_end = index + reader_macro.marker().len();
+    expand_reader_macro(
+      self.syntax_graph,
+      self.active_context_tag(),
+      self.text,
+      reader_macro,
+      index,
+    )
+    .map_or(marker_end, |(_, end)| end.max(marker_end))
+  }
+  fn unmatched_closer(
+    &mut self,
+    closer: Range<usize>,
+  ) -> Result<(), ParinferError> {
+    match self.mode {
+      ParinferMode::Indent => {
+        self.edits.push(TextEdit::new(closer, ""));
+        Ok(())
+      }
+      ParinferMode::Paren => Err(ParinferError::UnmatchedCloser(closer)),
+    }
+  }
+  fn flush_pending_line(&mut self) {
+    if let Some(pending_line) = self.pending_line.take() {
+      if !pending_line.closers.is_empty()
+        || pending_line.insertion != pending_line.removal_end
+      {
+        self.edits.push(TextEdit::new(
+          pending_line.insertion..pending_line.removal_end,
+          pending_line.closers,
+        ));
+      }
+    }
+  }
+  fn reset_line(&mut self, line_start: usize) {
+    self.line_start = line_start;
+    self.col_shift = 0;
+    self.code_end = None;
+    self.trail.clear();
+  }
+  fn start_line(&mut self, line_start: usize) {
+    self.reset_line(line_start);
+    self.enter_region(line_start);
+    let context = self.active_context();
+    if context.kind() != ContextKind::Code {
+      return;
+    }
+    let line = &self.text[line_start..];
+    let indent = line
+      .graphemes(true)
+      .take_while(|g| !g.contains('\n') && context.is_whitespace(g))
+      .map(|g| g.len())
+      .sum::<usize>();
+    if indent == line.len() || line[indent..].starts_with(['\n', '\r']) {
+      return;
+    }
+    match self.mode {
+      ParinferMode::Indent => {
+        while let Some(frame) = self.stack.last() {
+          if frame.kind == ContextKind::Code && frame.col >= indent {
+            let frame = self.stack.pop().unwrap();
+            if let Some(pending_line) = self.pending_line.as_mut() {
+              pending_line
+                .closers
+                .push_str(frame.encloser.closing_encloser_str());
+            }
+          } else {
+            break;
+          }
+        }
+      }
+      ParinferMode::Paren => {
+        let min_indent =
+          self.stack.last().map(|frame| frame.col + 1).unwrap_or(0);
+        let new_indent = self
+          .previous_trail_min_col
+          .map(|max_indent| indent.min(max_indent))
+          .unwrap_or(indent)
+          .max(min_indent);
+        if new_indent != indent {
+          self.edits.push(TextEdit::new(
+            line_start..line_start + indent,
+            " ".repeat(new_indent),
+          ));
+          self.col_shift = new_indent as isize - indent as isize;
+        }
+      }
+    }
+  }
+  fn end_line(&mut self, line_end: usize) {
+    let trail = std::mem::take(&mut self.trail);
+    match self.mode {
+      ParinferMode::Indent => {
+        let held_until = self
+          .cursor
+          .filter(|cursor| (self.line_start..=line_end).contains(cursor));
+        let (kept, removed): (Vec<_>, Vec<_>) =
+          trail.into_iter().partition(|(_, closer)| {
+            held_until.is_some_and(|cursor| closer.end <= cursor)
+          });
+        let removal_start = kept
+          .last()
+          .map(|(_, closer)| closer.end)
+          .or(self.code_end)
+          .or(removed.first().map(|(_, closer)| closer.start));
+        let removal_end = removed.last().map(|(_, closer)| closer.end);
+        for (frame, _) in removed.into_iter().rev() {
+          self.stack.push(frame);
+        }
+        if let Some(code_end) = self.code_end {
+          self.flush_pending_line();
+          let insertion = removal_start.unwrap_or(code_end);
+          self.pending_line = Some(PendingLine {
+            insertion,
+            removal_end: removal_end.unwrap_or(insertion),
+            closers: String::new(),
+          });
+        } else if let (Some(start), Some(end)) = (removal_start, removal_end) {
+          self.edits.push(TextEdit::new(start..end, ""));
+        }
+      }
+      ParinferMode::Paren => {
+        if self.code_end.is_some() {
+          self.previous_trail_min_col =
+            trail.iter().map(|(frame, _)| frame.col).min();
+        }
+      }
+    }
+  }
+  fn finish(mut self) -> Result<ParinferResult, ParinferError> {
+    while let Some(frame) = self.stack.pop() {
+      match (frame.kind, self.mode) {
+        (ContextKind::Comment, _) => continue,
+        (ContextKind::Code, ParinferMode::Indent) => {}
+        _ => return Err(ParinferError::UnclosedEncloser(frame.opener)),
+      }
+      if let Some(pending_line) = self.pending_line.as_mut() {
+        pending_line
+          .closers
+          .push_str(frame.encloser.closing_encloser_str());
+      }
+    }
+    self.flush_pending_line();
+    self
+      .edits
+      .sort_by_key(|edit| (edit.range.start, edit.range.end));
+    let mut edits: Vec<TextEdit> = vec![];
+    for edit in std::mem::take(&mut self.edits) {
+      match edits.last_mut() {
+        Some(previous_edit) if edit.range.start <= previous_edit.range.end => {
+          previous_edit.range.end = previous_edit.range.end.max(edit.range.end);
+          previous_edit.text.push_str(&edit.text);
+        }
+        _ => edits.push(edit),
+      }
+    }
+    self.edits = edits;
+    let mut text = String::with_capacity(self.text.len());
+    let mut copied_until = 0;
+    let mut cursor = self.cursor;
+    for edit in &self.edits {
+      text.push_str(&self.text[copied_until..edit.range.start]);
+      text.push_str(&edit.text);
+      copied_until = edit.range.end;
+    }
+    text.push_str(&self.text[copied_until..]);
+    if let Some(original_cursor) = self.cursor {
+      let shift = self
+        .edits
+        .iter()
+        .filter(|edit| edit.range.start < original_cursor)
+        .map(|edit| {
+          let removed = edit.range.end.min(original_cursor) - edit.range.start;
+          let inserted = if edit.range.end <= original_cursor {
+            edit.text.len()
+          } else {
+            edit.text.len().min(removed)
+          };
+          inserted as isize - removed as isize
+        })
+        .sum::<isize>();
+      cursor = Some((original_cursor as isize + shift) as usize);
+    }
+    Ok(ParinferResult {
+      text,
+      edits: self.edits,
+      cursor,
+    })
+  }
+  fn run(mut self) -> Result<ParinferResult, ParinferError> {
+    let text = self.text;
+    let mut skip_until = 0;
+    let mut escaped = false;
+    self.start_line(0);
+    for (index, grapheme) in text.grapheme_indices(true) {
+      if index < skip_until {
+        continue;
+      }
+      skip_until = index + grapheme.len();
+      self.enter_region(index);
+      if escaped {
+        escaped = false;
+        self.code(skip_until);
+      } else {
+        let awaited_closer = self
+          .stack
+          .last()
+          .map(|frame| frame.encloser.closing_encloser_str());
+        match Lexeme::lex(
+          self.active_context(),
+          awaited_closer,
+          &text[index..],
+          grapheme,
+        ) {
+          Lexeme::Escape => {
+            escaped = true;
+            self.code(skip_until);
+          }
+          Lexeme::Whitespace => {}
+          Lexeme::AwaitedCloser => {
+            skip_until = index + awaited_closer.unwrap().len();
+            self.close(index..skip_until);
+          }
+          Lexeme::ReaderMacro(reader_macro) => {
+            skip_until = self.reader_macro_end(reader_macro, index);
+            let span = &text[index..skip_until];
+            match span.rfind('\n') {
+              Some(newline) if newline + 1 < span.len() => {
+                self.end_line(index);
+                self.reset_line(index + newline + 1);
+                self.code(skip_until);
+                continue;
+              }
+              _ => self.code(skip_until),
+            }
+          }
+          Lexeme::Opener(encloser) => {
+            skip_until = index + encloser.opening_encloser_str().len();
+            self.open(encloser.clone(), index..skip_until);
+          }
+          Lexeme::Operator(operator) => {
+            skip_until = index + operator.op_str().len();
+            self.code(skip_until);
+          }
+          Lexeme::UnexpectedCloser(encloser) => {
+            skip_until = index + encloser.closing_encloser_str().len();
+            self.unmatched_closer(index..skip_until)?;
+          }
+          Lexeme::Terminal => self.code(skip_until),
+        }
+      }
+      if text[index..skip_until].contains('\n') {
+        self.end_line(index);
+        self.start_line(skip_until);
+      }
+    }
+    self.end_line(text.len());
+    self.finish()
+  }
+}
+
+pub fn parinfer<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_graph: &SyntaxGraph<C, E, O>,
+  text: &str,
+  mode: ParinferMode,
+  cursor: Option<usize>,
+) -> Result<ParinferResult, ParinferError> {
+  parinfer_in_regions(vec![(0, syntax_graph)], text, mode, cursor)
+}
+
+pub(crate) fn parinfer_in_regions<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_regions: Vec<(usize, &SyntaxGraph<C, E, O>)>,
+  text: &str,
+  mode: ParinferMode,
+  cursor: Option<usize>,
+) -> Result<ParinferResult, ParinferError> {
+  Parinfer::new(syntax_regions, text, mode, cursor).run()
+}
@@ -6,6 +6,8 @@ use std::{
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
+  lex::Lexeme,
+  reader_macro::{FormResult, ReaderMacro, ReaderMacroReader},
   syntax::{Encloser, EncloserOrOperator, Operator, SyntaxGraph},
   DocumentSyntaxTree,
 };
@@ -16,6 +18,7 @@ pub enum ParseError {
   UnexpectedCloser(String),
   OperatorMissingLeftArgument(String),
   OperatorMissingRightArgument(String),
+  ReaderMacroError { marker: String, message: String },
 }
 
 impl Display for ParseError {
@@ -35,10 +38,47 @@ impl Display for ParseError {
       OperatorMissingRightArgument(operator) => {
         write!(f, "operator \"{operator}\" missing right argument")
       }
+      ReaderMacroError { marker, message } => {
+        write!(f, "reader macro \"{marker}\" failed: {message}")
+      }
     }
   }
 }
 
+type OpenSexp<E, O> = (
+  usize,
+  EncloserOrOperator<E, O>,
+  Vec<DocumentSyntaxTree<E, O>>,
+);
+
+type Completion<E, O> =
+  Result<(Vec<DocumentSyntaxTree<E, O>>, usize), Vec<DocumentSyntaxTree<E, O>>>;
+
+pub(crate) fn expand_reader_macro<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_graph: &SyntaxGraph<C, E, O>,
+  context_tag: &C,
+  text: &str,
+  reader_macro: &ReaderMacro<E, O>,
+  index: usize,
+) -> Result<(Vec<DocumentSyntaxTree<E, O>>, usize), ParseError> {
+  let read_form = |index| {
+    Parse::new(syntax_graph, vec![], text)
+      .in_context(context_tag)
+      .read_form(index)
+  };
+  let mut reader = ReaderMacroReader::new(
+    text,
+    index..index + reader_macro.marker().len(),
+    &read_form,
+  );
+  let subtrees = reader_macro.expand(&mut reader)?;
+  Ok((subtrees, reader.position()))
+}
+
 pub(crate) struct Parse<
   't,
   'g,
@@ -49,11 +89,8 @@ pub(crate) struct Parse<
   text: &'t str,
   inherited_top_level_sexps: Vec<DocumentSyntaxTree<E, O>>,
   syntax_graph: &'g SyntaxGraph<C, E, O>,
-  open_sexps: Vec<(
-    usize,
-    EncloserOrOperator<E, O>,
-    Vec<DocumentSyntaxTree<E, O>>,
-  )>,
+  base_context: &'g C,
+  open_sexps: Vec<OpenSexp<E, O>>,
 }
 
 impl<
@@ -73,9 +110,22 @@ impl<
       text,
       inherited_top_level_sexps,
       syntax_graph,
+      base_context: &syntax_graph.root,
       open_sexps: vec![],
     }
   }
+  pub(crate) fn in_context(mut self, context_tag: &'g C) -> Self {
+    self.base_context = context_tag;
+    self
+  }
+  pub(crate) fn read_form(self, index: usize) -> FormResult<E, O> {
+    Ok(
+      self
+        .complete(index)?
+        .ok()
+        .and_then(|(mut sexps, end)| sexps.pop().map(|sexp| (sexp, end))),
+    )
+  }
   fn consume_left_sexps(
     &mut self,
     operator: &O,
@@ -172,16 +222,8 @@ impl<
   }
   pub(crate) fn complete(
     mut self,
-    already_parsed_index: usize,
-  ) -> Result<
-    Result<Vec<DocumentSyntaxTree<E, O>>, Vec<DocumentSyntaxTree<E, O>>>,
-    ParseError,
-  > {
-    let beginning_index = self
-      .inherited_top_level_sexps
-      .last()
-      .map(|syntax_tree| syntax_tree.range().end)
-      .unwrap_or(already_parsed_index);
+    beginning_index: usize,
+  ) -> Result<Completion<E, O>, ParseError> {
     if beginning_index >= self.text.len() {
       return Ok(Err(self.inherited_top_level_sexps));
     }
@@ -194,7 +236,7 @@ impl<
     let mut current_terminal_beginning: Option<usize> = None;
     let mut escaped = false;
 
-    'outer: while let Some((character_index_offset, character)) =
+    while let Some((character_index_offset, character)) =
       indexed_characters.next()
     {
       let character_index = beginning_index + character_index_offset;
@@ -210,7 +252,7 @@ impl<
             {
               let mut top_level_sexps = self.inherited_top_level_sexps;
               top_level_sexps.push(completed_sexp);
-              return Ok(Ok(top_level_sexps));
+              return Ok(Ok((top_level_sexps, character_index)));
             }
             current_terminal_beginning = None;
           }
@@ -224,110 +266,108 @@ impl<
         };
       }
 
-      let active_context = self.syntax_graph.get_context(
-        self
-          .open_sexps
-          .last()
-          .map(|(_, tag, _)| self.syntax_graph.get_context_tag(tag))
-          .unwrap_or(&self.syntax_graph.root),
-      );
+      let active_context_tag = self
+        .open_sexps
+        .last()
+        .map(|(_, tag, _)| self.syntax_graph.get_context_tag(tag))
+        .unwrap_or(self.base_context);
 
       if escaped {
         escaped = false;
-      } else if active_context
-        .escape_char
-        .as_ref()
-        .map(|char| char.as_str())
-        == Some(character)
-      {
-        escaped = true;
-        current_terminal_beginning =
-          current_terminal_beginning.or(Some(character_index));
-      } else if active_context.is_whitespace(character) {
-        finish_terminal!();
-      } else {
-        let remaining_text = &self.text[character_index..];
-
-        if let Some(awaited_closer) = self.awaited_closer() {
-          if remaining_text.starts_with(awaited_closer) {
-            let closer_len = awaited_closer.len();
-            finish_terminal!();
+        continue;
+      }
 
-            if let Some((_, encloser_or_operator, _)) = self.open_sexps.last() {
-              if let EncloserOrOperator::Operator(operator) =
-                encloser_or_operator
-              {
-                return Err(ParseError::OperatorMissingRightArgument(
-                  operator.op_str().to_string(),
-                ));
-              }
-            }
+      match Lexeme::lex(
+        self.syntax_graph.get_context(active_context_tag),
+        self.awaited_closer(),
+        &self.text[character_index..],
+        character,
+      ) {
+        Lexeme::Escape => {
+          escaped = true;
+          current_terminal_beginning =
+            current_terminal_beginning.or(Some(character_index));
+        }
+        Lexeme::Whitespace => finish_terminal!(),
+        Lexeme::AwaitedCloser => {
+          let closer_len = self.awaited_closer().unwrap().len();
+          finish_terminal!();
 
-            if let Some(completed_sexp) = self.close_sexp(character_index + 1) {
-              let mut top_level_sexps = self.inherited_top_level_sexps;
-              top_level_sexps.push(completed_sexp);
-              return Ok(Ok(top_level_sexps));
-            } else {
-              skip_n_chars!(closer_len);
-              continue;
+          if let Some((_, encloser_or_operator, _)) = self.open_sexps.last() {
+            if let EncloserOrOperator::Operator(operator) = encloser_or_operator
+            {
+              return Err(ParseError::OperatorMissingRightArgument(
+                operator.op_str().to_string(),
+              ));
             }
           }
-        }
-
-        let active_context_tag = self
-          .open_sexps
-          .last()
-          .map(|(_, tag, _)| self.syntax_graph.get_context_tag(tag))
-          .unwrap_or(&self.syntax_graph.root);
 
-        for encloser in self
-          .syntax_graph
-          .get_context(active_context_tag)
-          .enclosers()
-        {
-          let beginning_marker = encloser.opening_encloser_str();
-          if remaining_text.starts_with(beginning_marker) {
-            finish_terminal!();
-            self.open_sexps.push((
-              character_index,
-              EncloserOrOperator::Encloser(encloser.clone()),
-              vec![],
-            ));
-            skip_n_chars!(beginning_marker.len());
-            continue 'outer;
+          if let Some(completed_sexp) =
+            self.close_sexp(character_index + closer_len)
+          {
+            let mut top_level_sexps = self.inherited_top_level_sexps;
+            top_level_sexps.push(completed_sexp);
+            return Ok(Ok((top_level_sexps, character_index + closer_len)));
+          } else {
+            skip_n_chars!(closer_len);
           }
         }
-
-        for operator in self
-          .syntax_graph
-          .get_context(active_context_tag)
-          .operators()
-        {
-          let op_marker = operator.op_str();
-          if remaining_text.starts_with(op_marker) {
-            finish_terminal!();
-            let leftward_args = self.consume_left_sexps(operator)?;
-            self.open_sexps.push((
-              leftward_args
-                .first()
-                .map(|first_arg| first_arg.range().start)
-                .unwrap_or(character_index),
-              EncloserOrOperator::Operator(operator.clone()),
-              leftward_args,
-            ));
-            skip_n_chars!(op_marker.len());
-            continue 'outer;
+        Lexeme::ReaderMacro(reader_macro) => {
+          finish_terminal!();
+          let (subtrees, end) = expand_reader_macro(
+            self.syntax_graph,
+            active_context_tag,
+            self.text,
+            reader_macro,
+            character_index,
+          )?;
+          let mut completed_sexps = vec![];
+          for subtree in subtrees {
+            if let Some(completed_sexp) = self.push_closed_sexp(subtree) {
+              completed_sexps.push(completed_sexp);
+            }
           }
-        }
-
-        for closer in self.syntax_graph.get_closers(active_context_tag) {
-          if remaining_text.starts_with(closer) {
-            return Err(ParseError::UnexpectedCloser(closer.to_string()));
+          if !completed_sexps.is_empty() {
+            let mut top_level_sexps = self.inherited_top_level_sexps;
+            top_level_sexps.extend(completed_sexps);
+            return Ok(Ok((top_level_sexps, end)));
           }
+          while indexed_characters
+            .next_if(|(offset, _)| beginning_index + offset < end)
+            .is_some()
+          {}
         }
-
-        if current_terminal_beginning.is_none() {
-          current_terminal_beginning = Some(character_index)
+        Lexeme::Opener(encloser) => {
+          finish_terminal!();
+          self.open_sexps.push((
+            character_index,
+            EncloserOrOperator::Encloser(encloser.clone()),
+            vec![],
+          ));
+          skip_n_chars!(encloser.opening_encloser_str().len());
+        }
+        Lexeme::Operator(operator) => {
+          finish_terminal!();
+          let leftward_args = self.consume_left_sexps(operator)?;
+          self.open_sexps.push((
+            leftward_args
+              .first()
+              .map(|first_arg| first_arg.range().start)
+              .unwrap_or(character_index),
+            EncloserOrOperator::Operator(operator.clone()),
+            leftward_args,
+          ));
+          skip_n_chars!(operator.op_str().len());
+        }
+        Lexeme::UnexpectedCloser(encloser) => {
+          return Err(ParseError::UnexpectedCloser(
+            encloser.closing_encloser_str().to_string(),
+          ));
+        }
+        Lexeme::Terminal => {
+          if current_terminal_beginning.is_none() {
+            current_terminal_beginning = Some(character_index)
+          }
         }
       }
     }
@@ -5,25 +5,20 @@ use crate::{
 use std::{fmt::Debug, hash::Hash};
 
 #[derive(Debug, Clone)]
-pub struct Parser<
-  't,
-  C: Clone + Debug + PartialEq + Eq + Hash,
-  E: Encloser,
-  O: Operator,
-> {
-  pub(crate) text: &'t str,
-  pub(crate) syntax_graph: SyntaxGraph<C, E, O>,
+pub(crate) struct TopLevelReader<E: Encloser, O: Operator> {
   parsed_top_level_sexps: Vec<DocumentSyntaxTree<E, O>>,
   top_level_lookahead: usize,
   already_parsed_index: usize,
+  parse_index: usize,
 }
 
-impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
-  Parser<'t, C, E, O>
-{
-  pub fn new(syntax_graph: SyntaxGraph<C, E, O>, text: &'t str) -> Self {
+impl<E: Encloser, O: Operator> TopLevelReader<E, O> {
+  pub(crate) fn new<C: Clone + Debug + PartialEq + Eq + Hash>(
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    already_parsed_index: usize,
+  ) -> Self {
     Self {
-      text,
+      parsed_top_level_sexps: vec![],
       top_level_lookahead: syntax_graph
         .get_context(&syntax_graph.root)
         .operators()
@@ -31,20 +26,29 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
         .map(|operator| operator.left_args())
         .max()
         .unwrap_or(0),
-      syntax_graph,
-      parsed_top_level_sexps: vec![],
-      already_parsed_index: 0,
+      already_parsed_index,
+      parse_index: already_parsed_index,
     }
   }
-  pub fn replace_syntax_graph(
-    &mut self,
-    new_syntax_graph: SyntaxGraph<C, E, O>,
-  ) {
-    self.syntax_graph = new_syntax_graph;
+  pub(crate) fn top_level_lookahead(&self) -> usize {
+    self.top_level_lookahead
+  }
+  pub(crate) fn resume_index(&self) -> usize {
+    self
+      .parsed_top_level_sexps
+      .first()
+      .map(|sexp| sexp.range().start)
+      .unwrap_or(self.parse_index)
+  }
+  pub(crate) fn resume_at(&mut self, index: usize) {
     self.parsed_top_level_sexps.clear();
+    self.already_parsed_index = index;
+    self.parse_index = index;
   }
-  pub fn read_next(
+  pub(crate) fn read_next<C: Clone + Debug + PartialEq + Eq + Hash>(
     &mut self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    text: &str,
   ) -> Result<Option<DocumentSyntaxTree<E, O>>, ParseError> {
     while self.parsed_top_level_sexps.len() <= self.top_level_lookahead {
       let mut stolen_top_level_sexps = vec![];
@@ -52,11 +56,12 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
         &mut stolen_top_level_sexps,
         &mut self.parsed_top_level_sexps,
       );
-      match Parse::new(&self.syntax_graph, stolen_top_level_sexps, &self.text)
-        .complete(self.already_parsed_index)?
+      match Parse::new(syntax_graph, stolen_top_level_sexps, text)
+        .complete(self.parse_index)?
       {
-        Ok(new_top_level_sexps) => {
+        Ok((new_top_level_sexps, parse_index)) => {
           self.parsed_top_level_sexps = new_top_level_sexps;
+          self.parse_index = parse_index;
         }
         Err(original_top_level_sexps) => {
           self.parsed_top_level_sexps = original_top_level_sexps;
@@ -64,19 +69,22 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
         }
       }
     }
-    Ok(if let Some(sexp) = self.parsed_top_level_sexps.pop() {
+    Ok(if self.parsed_top_level_sexps.is_empty() {
+      None
+    } else {
+      let sexp = self.parsed_top_level_sexps.remove(0);
       self.already_parsed_index = sexp.range().end;
       Some(sexp)
-    } else {
-      None
     })
   }
-  pub fn read_all(
+  pub(crate) fn read_all<C: Clone + Debug + PartialEq + Eq + Hash>(
     &mut self,
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    text: &str,
   ) -> Vec<Result<DocumentSyntaxTree<E, O>, ParseError>> {
     let mut results = vec![];
     loop {
-      match self.read_next() {
+      match self.read_next(syntax_graph, text) {
         Ok(None) => break,
         Ok(Some(tagged_sexp)) => results.push(Ok(tagged_sexp)),
         Err(err) => {
@@ -87,6 +95,59 @@ impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
     }
     results
   }
+}
+
+#[derive(Debug, Clone)]
+pub struct Parser<
+  't,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  pub(crate) text: &'t str,
+  pub(crate) syntax_graph: SyntaxGraph<C, E, O>,
+  pub(crate) reader: TopLevelReader<E, O>,
+}
+
+impl<'t, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  Parser<'t, C, E, O>
+{
+  pub fn new(syntax_graph: SyntaxGraph<C, E, O>, text: &'t str) -> Self {
+    Self {
+      text,
+      reader: TopLevelReader::new(&syntax_graph, 0),
+      syntax_graph,
+    }
+  }
+  pub fn syntax_graph(&self) -> &SyntaxGraph<C, E, O> {
+    &self.syntax_graph
+  }
+  pub fn replace_syntax_graph(
+    &mut self,
+    new_syntax_graph: SyntaxGraph<C, E, O>,
+  ) {
+    self.reader =
+      TopLevelReader::new(&new_syntax_graph, self.reader.already_parsed_index);
+    self.syntax_graph = new_syntax_graph;
+  }
+  pub fn extend_syntax_graph(
+    &mut self,
+    extend: impl FnOnce(&mut SyntaxGraph<C, E, O>),
+  ) {
+    let mut syntax_graph = self.syntax_graph.clone();
+    extend(&mut syntax_graph);
+    self.replace_syntax_graph(syntax_graph);
+  }
+  pub fn read_next(
+    &mut self,
+  ) -> Result<Option<DocumentSyntaxTree<E, O>>, ParseError> {
+    self.reader.read_next(&self.syntax_graph, self.text)
+  }
+  pub fn read_all(
+    &mut self,
+  ) -> Vec<Result<DocumentSyntaxTree<E, O>, ParseError>> {
+    self.reader.read_all(&self.syntax_graph, self.text)
+  }
   pub fn read_next_sexp(&mut self) -> Result<Option<RawSexp>, ParseError> {
     self.read_next().map(|maybe_tagged_sexp| {
       maybe_tagged_sexp.map(|tagged_sexp| tagged_sexp.into())
@@ -0,0 +1,308 @@
+use std::{fmt::Debug, hash::Hash};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{
+  syntax::EncloserOrOperator, Encloser, Operator, Sexp, SyntaxContext,
+  SyntaxGraph, SyntaxTree,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Doc {
+  Text(String),
+  Line,
+  SoftLine,
+  HardLine,
+  Concat(Vec<Doc>),
+  Nest(usize, Box<Doc>),
+  Align(Box<Doc>),
+  Group(Box<Doc>),
+  IfBreak(Box<Doc>, Box<Doc>),
+}
+
+pub(crate) fn text_width(text: &str) -> usize {
+  text.graphemes(true).count()
+}
+
+fn fits(
+  mut remaining_width: isize,
+  doc: &Doc,
+  rest: &[(usize, bool, &Doc)],
+) -> bool {
+  let mut stack = vec![(true, doc)];
+  let mut rest = rest.iter().rev();
+  loop {
+    if remaining_width < 0 {
+      return false;
+    }
+    let Some((flat, doc)) = stack
+      .pop()
+      .or_else(|| rest.next().map(|&(_, flat, doc)| (flat, doc)))
+    else {
+      return true;
+    };
+    match doc {
+      Doc::Text(text) => {
+        if let Some(newline_index) = text.find('\n') {
+          return text_width(&text[..newline_index]) as isize
+            <= remaining_width;
+        }
+        remaining_width -= text_width(text) as isize;
+      }
+      Doc::Line => {
+        if flat {
+          remaining_width -= 1;
+        } else {
+          return true;
+        }
+      }
+      Doc::SoftLine => {
+        if !flat {
+          return true;
+        }
+      }
+      Doc::HardLine => return !flat,
+      Doc::Concat(docs) => {
+        stack.extend(docs.iter().rev().map(|doc| (flat, doc)));
+      }
+      Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) => {
+        stack.push((flat, doc))
+      }
+      Doc::IfBreak(broken, flat_doc) => {
+        stack.push((flat, if flat { flat_doc } else { broken }))
+      }
+    }
+  }
+}
+
+impl Doc {
+  pub fn text(text: impl Into<String>) -> Self {
+    Self::Text(text.into())
+  }
+  pub fn nest(indent: usize, doc: Doc) -> Self {
+    Self::Nest(indent, Box::new(doc))
+  }
+  pub fn align(doc: Doc) -> Self {
+    Self::Align(Box::new(doc))
+  }
+  pub fn group(doc: Doc) -> Self {
+    Self::Group(Box::new(doc))
+  }
+  pub fn if_break(broken: Doc, flat: Doc) -> Self {
+    Self::IfBreak(Box::new(broken), Box::new(flat))
+  }
+  pub fn flat_width(&self) -> Option<usize> {
+    match self {
+      Doc::Text(text) => (!text.contains('\n')).then(|| text_width(text)),
+      Doc::Line => Some(1),
+      Doc::SoftLine => Some(0),
+      Doc::HardLine => None,
+      Doc::Concat(docs) => docs.iter().map(Doc::flat_width).sum(),
+      Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) => doc.flat_width(),
+      Doc::IfBreak(_, flat) => flat.flat_width(),
+    }
+  }
+  pub fn ends_with_hard_line(&self) -> bool {
+    match self {
+      Doc::HardLine => true,
+      Doc::Concat(docs) => docs.last().is_some_and(Doc::ends_with_hard_line),
+      Doc::Nest(_, doc) | Doc::Align(doc) | Doc::Group(doc) => {
+        doc.ends_with_hard_line()
+      }
+      Doc::IfBreak(broken, flat) => {
+        broken.ends_with_hard_line() && flat.ends_with_hard_line()
+      }
+      _ => false,
+    }
+  }
+  pub fn render(&self, width: usize) -> String {
+    let mut output = String::new();
+    let mut column = 0;
+    let mut stack: Vec<(usize, bool, &Doc)> = vec![(0, false, self)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+      let newline = |output: &mut String, column: &mut usize| {
+        output.push('\n');
+        output.push_str(&" ".repeat(indent));
+        *column = indent;
+      };
+      match doc {
+        Doc::Text(text) => {
+          output.push_str(text);
+          column = match text.rfind('\n') {
+            Some(newline_index) => text_width(&text[newline_index + 1..]),
+            None => column + text_width(text),
+          };
+        }
+        Doc::Line => {
+          if flat {
+            output.push(' ');
+            column += 1;
+          } else {
+            newline(&mut output, &mut column);
+          }
+        }
+        Doc::SoftLine => {
+          if !flat {
+            newline(&mut output, &mut column);
+          }
+        }
+        Doc::HardLine => newline(&mut output, &mut column),
+        Doc::Concat(docs) => {
+          stack.extend(docs.iter().rev().map(|doc| (indent, flat, doc)));
+        }
+        Doc::Nest(nested_indent, doc) => {
+          stack.push((indent + nested_indent, flat, doc))
+        }
+        Doc::Align(doc) => stack.push((column, flat, doc)),
+        Doc::Group(doc) => {
+          let flat =
+            flat || fits(width as isize - column as isize, doc, &stack);
+          stack.push((indent, flat, doc));
+        }
+        Doc::IfBreak(broken, flat_doc) => {
+          stack.push((indent, flat, if flat { flat_doc } else { broken }))
+        }
+      }
+    }
+    output
+  }
+}
+
+pub struct PrettyPrinter<
+  'g,
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+> {
+  syntax_graph: &'g SyntaxGraph<C, E, O>,
+  width: usize,
+}
+
+impl<'g, C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  PrettyPrinter<'g, C, E, O>
+{
+  pub fn new(syntax_graph: &'g SyntaxGraph<C, E, O>, width: usize) -> Self {
+    Self {
+      syntax_graph,
+      width,
+    }
+  }
+  pub fn syntax_graph(&self) -> &'g SyntaxGraph<C, E, O> {
+    self.syntax_graph
+  }
+  pub fn width(&self) -> usize {
+    self.width
+  }
+  pub(crate) fn separator(
+    context: &SyntaxContext<E, O>,
+    breakable: bool,
+  ) -> Doc {
+    match (context.is_whitespace(" "), context.is_whitespace("\n")) {
+      (true, true) if breakable => Doc::Line,
+      (_, true) => Doc::HardLine,
+      (true, false) => Doc::text(" "),
+      (false, false) => Doc::Concat(vec![]),
+    }
+  }
+  pub(crate) fn join_with(docs: Vec<Doc>, separator: Doc) -> Doc {
+    let mut joined = vec![];
+    for doc in docs {
+      if joined
+        .last()
+        .is_some_and(|previous: &Doc| !previous.ends_with_hard_line())
+      {
+        joined.push(separator.clone());
+      }
+      joined.push(doc);
+    }
+    Doc::Concat(joined)
+  }
+  pub(crate) fn join(
+    context: &SyntaxContext<E, O>,
+    docs: Vec<Doc>,
+    breakable: bool,
+  ) -> Doc {
+    Self::join_with(docs, Self::separator(context, breakable))
+  }
+  pub(crate) fn default_body(
+    encloser: &E,
+    context: &SyntaxContext<E, O>,
+    children: Vec<Doc>,
+  ) -> Doc {
+    Doc::nest(
+      text_width(encloser.opening_encloser_str()),
+      Self::join(context, children, true),
+    )
+  }
+  pub(crate) fn encloser_doc(
+    &self,
+    encloser: &E,
+    children: Vec<Doc>,
+    body: impl FnOnce(&SyntaxContext<E, O>, Vec<Doc>) -> Doc,
+  ) -> Doc {
+    let context = self
+      .syntax_graph
+      .get_context(self.syntax_graph.get_encloser_context_tag(encloser));
+    let closer = encloser.closing_encloser_str();
+    let (closer, ends_line) = match closer.strip_suffix('\n') {
+      Some(closer) => (closer, true),
+      None => (closer, false),
+    };
+    let doc = Doc::align(Doc::group(Doc::Concat(vec![
+      Doc::text(encloser.opening_encloser_str()),
+      body(context, children),
+      Doc::text(closer),
+    ])));
+    if ends_line {
+      Doc::Concat(vec![doc, Doc::HardLine])
+    } else {
+      doc
+    }
+  }
+  pub(crate) fn operator_doc(&self, operator: &O, children: Vec<Doc>) -> Doc {
+    let context = self
+      .syntax_graph
+      .get_context(self.syntax_graph.get_operator_context_tag(operator));
+    let mut left_args = children;
+    let right_args =
+      left_args.split_off(operator.left_args().min(left_args.len()));
+    Doc::group(Doc::Concat(vec![
+      Self::join(context, left_args, true),
+      Doc::text(operator.op_str()),
+      Self::join(context, right_args, true),
+    ]))
+  }
+  pub fn doc(&self, tree: &SyntaxTree<E, O>) -> Doc {
+    match tree {
+      Sexp::Leaf((), leaf) => Doc::text(leaf),
+      Sexp::Inner(encloser_or_operator, children) => {
+        let children = children.iter().map(|child| self.doc(child)).collect();
+        match encloser_or_operator {
+          EncloserOrOperator::Encloser(encloser) => {
+            self.encloser_doc(encloser, children, |context, children| {
+              Self::default_body(encloser, context, children)
+            })
+          }
+          EncloserOrOperator::Operator(operator) => {
+            self.operator_doc(operator, children)
+          }
+        }
+      }
+    }
+  }
+  pub fn print(&self, tree: &SyntaxTree<E, O>) -> String {
+    self.doc(tree).render(self.width)
+  }
+  pub(crate) fn top_level_doc(&self, docs: Vec<Doc>) -> Doc {
+    Self::join(
+      self.syntax_graph.get_context(&self.syntax_graph.root),
+      docs,
+      false,
+    )
+  }
+  pub fn print_all(&self, trees: &[SyntaxTree<E, O>]) -> String {
+    self
+      .top_level_doc(trees.iter().map(|tree| self.doc(tree)).collect())
+      .render(self.width)
+  }
+}
@@ -0,0 +1,442 @@
+use std::{
+  collections::HashMap,
+  fmt::{Debug, Display},
+  hash::Hash,
+  ops::Range,
+};
+
+use crate::{
+  syntax::EncloserOrOperator, DocumentSyntaxTree, Encloser, Operator,
+  ParseError, Parser, Sexp, SyntaxGraph, SyntaxTree,
+};
+
+pub trait QueryNode<E: Encloser, O: Operator> {
+  fn encloser_or_operator(&self) -> Option<&EncloserOrOperator<E, O>>;
+  fn node_range(&self) -> Option<Range<usize>>;
+}
+
+impl<E: Encloser, O: Operator> QueryNode<E, O> for SyntaxTree<E, O> {
+  fn encloser_or_operator(&self) -> Option<&EncloserOrOperator<E, O>> {
+    match self {
+      Sexp::Leaf(_, _) => None,
+      Sexp::Inner(encloser_or_operator, _) => Some(encloser_or_operator),
+    }
+  }
+  fn node_range(&self) -> Option<Range<usize>> {
+    None
+  }
+}
+
+impl<E: Encloser, O: Operator> QueryNode<E, O> for DocumentSyntaxTree<E, O> {
+  fn encloser_or_operator(&self) -> Option<&EncloserOrOperator<E, O>> {
+    match self {
+      Sexp::Leaf(_, _) => None,
+      Sexp::Inner((_, encloser_or_operator), _) => Some(encloser_or_operator),
+    }
+  }
+  fn node_range(&self) -> Option<Range<usize>> {
+    Some(self.range().clone())
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repetition {
+  One,
+  ZeroOrMore,
+  OneOrMore,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KindConstraint {
+  Leaf,
+  Tag(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternNode<E: Encloser, O: Operator> {
+  Literal(String),
+  Wildcard {
+    capture: Option<String>,
+    kind: Option<KindConstraint>,
+    repetition: Repetition,
+  },
+  Inner(EncloserOrOperator<E, O>, Vec<PatternNode<E, O>>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatternError {
+  Parse(ParseError),
+  Empty,
+  MultipleForms,
+  TopLevelRepetition,
+}
+
+impl Display for PatternError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use PatternError::*;
+    match self {
+      Parse(parse_error) => write!(f, "couldn't parse pattern: {parse_error}"),
+      Empty => write!(f, "pattern is empty"),
+      MultipleForms => write!(f, "pattern contains more than one form"),
+      TopLevelRepetition => {
+        write!(f, "pattern can't be a repetition at the top level")
+      }
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Captured<
+  't,
+  L: Clone + PartialEq + Eq + Debug,
+  I: Clone + PartialEq + Eq + Debug,
+> {
+  pub path: Vec<usize>,
+  pub range: Option<Range<usize>>,
+  pub node: &'t Sexp<L, I>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match<
+  't,
+  L: Clone + PartialEq + Eq + Debug,
+  I: Clone + PartialEq + Eq + Debug,
+> {
+  pub path: Vec<usize>,
+  pub range: Option<Range<usize>>,
+  pub node: &'t Sexp<L, I>,
+  pub captures: HashMap<String, Vec<Captured<'t, L, I>>>,
+}
+
+pub type DocumentMatch<'t, E, O> =
+  Match<'t, Range<usize>, (Range<usize>, EncloserOrOperator<E, O>)>;
+
+impl<
+    't,
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+  > Match<'t, L, I>
+{
+  pub fn capture(&self, name: &str) -> Option<&Captured<'t, L, I>> {
+    self
+      .captures
+      .get(name)
+      .and_then(|captured| captured.first())
+  }
+}
+
+type Captures<'t, L, I> = Vec<(String, Vec<Captured<'t, L, I>>)>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern<E: Encloser, O: Operator> {
+  root: PatternNode<E, O>,
+}
+
+fn pattern_node<E: Encloser, O: Operator>(
+  tree: &SyntaxTree<E, O>,
+) -> PatternNode<E, O> {
+  match tree {
+    // `??name` matches the literal leaf `?name`, and `??_` matches a literal
+    // `_`, since `?_` is never useful as a capture name.
+    Sexp::Leaf((), leaf) if leaf == "??_" => PatternNode::Literal("_".into()),
+    Sexp::Leaf((), leaf) if leaf.starts_with("??") => {
+      PatternNode::Literal(leaf[1..].to_string())
+    }
+    Sexp::Leaf((), leaf) => {
+      let Some(wildcard) = leaf
+        .strip_prefix('?')
+        .or_else(|| leaf.starts_with('_').then_some(leaf.as_str()))
+      else {
+        return PatternNode::Literal(leaf.clone());
+      };
+      let (wildcard, repetition) = if let Some(w) = wildcard.strip_suffix('*') {
+        (w, Repetition::ZeroOrMore)
+      } else if let Some(w) = wildcard.strip_suffix('+') {
+        (w, Repetition::OneOrMore)
+      } else {
+        (wildcard, Repetition::One)
+      };
+      let (name, kind) = match wildcard.split_once(':') {
+        Some((name, "leaf")) => (name, Some(KindConstraint::Leaf)),
+        Some((name, tag)) => (name, Some(KindConstraint::Tag(tag.to_string()))),
+        None => (wildcard, None),
+      };
+      if leaf.starts_with('?') {
+        PatternNode::Wildcard {
+          capture: Some(name.to_string()),
+          kind,
+          repetition,
+        }
+      } else if name == "_" {
+        PatternNode::Wildcard {
+          capture: None,
+          kind,
+          repetition,
+        }
+      } else {
+        PatternNode::Literal(leaf.clone())
+      }
+    }
+    Sexp::Inner(encloser_or_operator, children) => PatternNode::Inner(
+      encloser_or_operator.clone(),
+      children.iter().map(pattern_node).collect(),
+    ),
+  }
+}
+
+impl<E: Encloser, O: Operator> Pattern<E, O> {
+  pub fn from_syntax_tree(
+    tree: &SyntaxTree<E, O>,
+  ) -> Result<Self, PatternError> {
+    let root = pattern_node(tree);
+    if let PatternNode::Wildcard {
+      repetition: Repetition::ZeroOrMore | Repetition::OneOrMore,
+      ..
+    } = root
+    {
+      return Err(PatternError::TopLevelRepetition);
+    }
+    Ok(Self { root })
+  }
+  pub fn parse<C: Clone + Debug + PartialEq + Eq + Hash>(
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    text: &str,
+  ) -> Result<Self, PatternError> {
+    let mut trees = Parser::new(syntax_graph.clone(), text)
+      .read_all()
+      .into_iter()
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(PatternError::Parse)?;
+    if trees.len() > 1 {
+      return Err(PatternError::MultipleForms);
+    }
+    let tree = trees.pop().ok_or(PatternError::Empty)?;
+    Self::from_syntax_tree(&tree.into())
+  }
+  pub fn root(&self) -> &PatternNode<E, O> {
+    &self.root
+  }
+  fn satisfies_kind<L, I>(
+    kind: &Option<KindConstraint>,
+    node: &Sexp<L, I>,
+  ) -> bool
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    match kind {
+      None => true,
+      Some(KindConstraint::Leaf) => matches!(node, Sexp::Leaf(_, _)),
+      Some(KindConstraint::Tag(tag)) => node
+        .encloser_or_operator()
+        .is_some_and(|encloser_or_operator| {
+          encloser_or_operator.id_str() == tag
+        }),
+    }
+  }
+  fn same_tree<L, I>(a: &Sexp<L, I>, b: &Sexp<L, I>) -> bool
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    match (a, b) {
+      (Sexp::Leaf(_, a_leaf), Sexp::Leaf(_, b_leaf)) => a_leaf == b_leaf,
+      (Sexp::Inner(_, a_children), Sexp::Inner(_, b_children)) => {
+        a.encloser_or_operator() == b.encloser_or_operator()
+          && a_children.len() == b_children.len()
+          && a_children
+            .iter()
+            .zip(b_children)
+            .all(|(a_child, b_child)| Self::same_tree(a_child, b_child))
+      }
+      _ => false,
+    }
+  }
+  fn bind<'t, L, I>(
+    captures: &mut Captures<'t, L, I>,
+    name: &str,
+    captured: Vec<Captured<'t, L, I>>,
+  ) -> bool
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    match captures.iter().find(|(bound_name, _)| bound_name == name) {
+      Some((_, bound)) => {
+        bound.len() == captured.len()
+          && bound
+            .iter()
+            .zip(&captured)
+            .all(|(bound, captured)| Self::same_tree(bound.node, captured.node))
+      }
+      None => {
+        captures.push((name.to_string(), captured));
+        true
+      }
+    }
+  }
+  fn match_node<'t, L, I>(
+    pattern: &PatternNode<E, O>,
+    node: &'t Sexp<L, I>,
+    path: &[usize],
+    captures: &mut Captures<'t, L, I>,
+  ) -> bool
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    match pattern {
+      PatternNode::Literal(literal) => {
+        matches!(node, Sexp::Leaf(_, leaf) if leaf == literal)
+      }
+      PatternNode::Wildcard { capture, kind, .. } => {
+        if !Self::satisfies_kind(kind, node) {
+          return false;
+        }
+        match capture {
+          Some(name) => Self::bind(
+            captures,
+            name,
+            vec![Captured {
+              path: path.to_vec(),
+              range: node.node_range(),
+              node,
+            }],
+          ),
+          None => true,
+        }
+      }
+      PatternNode::Inner(encloser_or_operator, child_patterns) => {
+        let Sexp::Inner(_, children) = node else {
+          return false;
+        };
+        node.encloser_or_operator() == Some(encloser_or_operator)
+          && Self::match_sequence(child_patterns, children, 0, path, captures)
+      }
+    }
+  }
+  fn match_sequence<'t, L, I>(
+    patterns: &[PatternNode<E, O>],
+    nodes: &'t [Sexp<L, I>],
+    offset: usize,
+    parent_path: &[usize],
+    captures: &mut Captures<'t, L, I>,
+  ) -> bool
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    let child_path = |i: usize| {
+      let mut path = parent_path.to_vec();
+      path.push(offset + i);
+      path
+    };
+    let Some((pattern, rest)) = patterns.split_first() else {
+      return nodes.is_empty();
+    };
+    let bound = captures.len();
+    if let PatternNode::Wildcard {
+      capture,
+      kind,
+      repetition: repetition @ (Repetition::ZeroOrMore | Repetition::OneOrMore),
+    } = pattern
+    {
+      let minimum = if *repetition == Repetition::OneOrMore {
+        1
+      } else {
+        0
+      };
+      let maximum = nodes
+        .iter()
+        .position(|node| !Self::satisfies_kind(kind, node))
+        .unwrap_or(nodes.len());
+      for count in (minimum..=maximum).rev() {
+        let bound_repetition = capture.as_ref().is_none_or(|name| {
+          Self::bind(
+            captures,
+            name,
+            nodes[..count]
+              .iter()
+              .enumerate()
+              .map(|(i, node)| Captured {
+                path: child_path(i),
+                range: node.node_range(),
+                node,
+              })
+              .collect(),
+          )
+        });
+        if bound_repetition
+          && Self::match_sequence(
+            rest,
+            &nodes[count..],
+            offset + count,
+            parent_path,
+            captures,
+          )
+        {
+          return true;
+        }
+        captures.truncate(bound);
+      }
+      false
+    } else {
+      let matched = nodes.first().is_some_and(|node| {
+        Self::match_node(pattern, node, &child_path(0), captures)
+      }) && Self::match_sequence(
+        rest,
+        &nodes[1..],
+        offset + 1,
+        parent_path,
+        captures,
+      );
+      if !matched {
+        captures.truncate(bound);
+      }
+      matched
+    }
+  }
+  pub fn match_at<'t, L, I>(
+    &self,
+    node: &'t Sexp<L, I>,
+    path: Vec<usize>,
+  ) -> Option<Match<'t, L, I>>
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    let mut captures = vec![];
+    Self::match_node(&self.root, node, &path, &mut captures).then(|| Match {
+      range: node.node_range(),
+      path,
+      node,
+      captures: captures.into_iter().collect(),
+    })
+  }
+  pub fn find_all<'t, L, I>(
+    &self,
+    trees: &'t [Sexp<L, I>],
+  ) -> Vec<Match<'t, L, I>>
+  where
+    L: Clone + PartialEq + Eq + Debug,
+    I: Clone + PartialEq + Eq + Debug,
+    Sexp<L, I>: QueryNode<E, O>,
+  {
+    trees
+      .iter()
+      .enumerate()
+      .flat_map(|(tree_index, tree)| {
+        tree.pre_order().filter_map(move |(mut path, node)| {
+          path.insert(0, tree_index);
+          self.match_at(node, path)
+        })
+      })
+      .collect()
+  }
+}
@@ -0,0 +1,137 @@
+use std::{fmt::Debug, ops::Range, sync::Arc};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{DocumentSyntaxTree, Encloser, Operator, ParseError};
+
+pub type ReaderMacroResult<E, O> =
+  Result<Vec<DocumentSyntaxTree<E, O>>, ParseError>;
+
+pub(crate) type FormResult<E, O> =
+  Result<Option<(DocumentSyntaxTree<E, O>, usize)>, ParseError>;
+
+pub(crate) type FormReader<'r, E, O> = dyn Fn(usize) -> FormResult<E, O> + 'r;
+
+type ReaderMacroFn<E, O> =
+  dyn Fn(&mut ReaderMacroReader<E, O>) -> ReaderMacroResult<E, O> + Send + Sync;
+
+#[derive(Clone)]
+pub struct ReaderMacro<E: Encloser, O: Operator> {
+  marker: String,
+  callback: Arc<ReaderMacroFn<E, O>>,
+}
+
+impl<E: Encloser, O: Operator> ReaderMacro<E, O> {
+  pub fn new<F>(marker: impl Into<String>, callback: F) -> Self
+  where
+    F: Fn(&mut ReaderMacroReader<E, O>) -> ReaderMacroResult<E, O>
+      + Send
+      + Sync
+      + 'static,
+  {
+    Self {
+      marker: marker.into(),
+      callback: Arc::new(callback),
+    }
+  }
+  pub fn marker(&self) -> &str {
+    &self.marker
+  }
+  pub(crate) fn expand(
+    &self,
+    reader: &mut ReaderMacroReader<E, O>,
+  ) -> ReaderMacroResult<E, O> {
+    (self.callback)(reader)
+  }
+}
+
+impl<E: Encloser, O: Operator> Debug for ReaderMacro<E, O> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("ReaderMacro")
+      .field("marker", &self.marker)
+      .finish_non_exhaustive()
+  }
+}
+
+pub struct ReaderMacroReader<'r, E: Encloser, O: Operator> {
+  text: &'r str,
+  marker_range: Range<usize>,
+  position: usize,
+  read_form: &'r FormReader<'r, E, O>,
+}
+
+impl<'r, E: Encloser, O: Operator> ReaderMacroReader<'r, E, O> {
+  pub(crate) fn new(
+    text: &'r str,
+    marker_range: Range<usize>,
+    read_form: &'r FormReader<'r, E, O>,
+  ) -> Self {
+    Self {
+      text,
+      position: marker_range.end,
+      marker_range,
+      read_form,
+    }
+  }
+  pub fn text(&self) -> &'r str {
+    self.text
+  }
+  pub fn marker_range(&self) -> Range<usize> {
+    self.marker_range.clone()
+  }
+  pub fn position(&self) -> usize {
+    self.position
+  }
+  pub fn remaining_text(&self) -> &'r str {
+    &self.text[self.position..]
+  }
+  pub fn advance(&mut self, len: usize) -> Range<usize> {
+    let start = self.position;
+    self.position += std::iter::once(0)
+      .chain(
+        self
+          .remaining_text()
+          .grapheme_indices(true)
+          .map(|(index, grapheme)| index + grapheme.len()),
+      )
+      .find(|&end| end >= len)
+      .unwrap_or(self.remaining_text().len());
+    start..self.position
+  }
+  pub fn read_while(
+    &mut self,
+    mut predicate: impl FnMut(&str) -> bool,
+  ) -> Range<usize> {
+    let len = self
+      .remaining_text()
+      .graphemes(true)
+      .take_while(|grapheme| predicate(grapheme))
+      .map(str::len)
+      .sum();
+    self.advance(len)
+  }
+  pub fn leaf(&self, range: Range<usize>) -> DocumentSyntaxTree<E, O> {
+    DocumentSyntaxTree::Leaf(range.clone(), self.text[range].to_string())
+  }
+  pub fn read_leaf(
+    &mut self,
+    predicate: impl FnMut(&str) -> bool,
+  ) -> Option<DocumentSyntaxTree<E, O>> {
+    let range = self.read_while(predicate);
+    (!range.is_empty()).then(|| self.leaf(range))
+  }
+  pub fn read_form(
+    &mut self,
+  ) -> Result<Option<DocumentSyntaxTree<E, O>>, ParseError> {
+    Ok((self.read_form)(self.position)?.map(|(form, end)| {
+      self.position = end;
+      form
+    }))
+  }
+  pub fn error(&self, message: impl Into<String>) -> ParseError {
+    ParseError::ReaderMacroError {
+      marker: self.text[self.marker_range.clone()].to_string(),
+      message: message.into(),
+    }
+  }
+}
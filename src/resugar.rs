@@ -0,0 +1,183 @@
+use std::{
+  fmt::{Debug, Display},
+  hash::Hash,
+};
+
+use crate::{
+  desugar::{DesugarStrategy, PrefixTag},
+  syntax::EncloserOrOperator,
+  Encloser, Operator, RawSexp, Sexp, SyntaxGraph, SyntaxTree,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResugarError {
+  UnrecognizedForm {
+    path: Vec<usize>,
+  },
+  OperatorArity {
+    path: Vec<usize>,
+    operator: String,
+    expected: usize,
+    found: usize,
+  },
+}
+
+impl ResugarError {
+  pub fn path(&self) -> &[usize] {
+    match self {
+      ResugarError::UnrecognizedForm { path } => path,
+      ResugarError::OperatorArity { path, .. } => path,
+    }
+  }
+}
+
+impl Display for ResugarError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use ResugarError::*;
+    match self {
+      UnrecognizedForm { path } => {
+        write!(f, "form at {path:?} has no matching encloser or operator")
+      }
+      OperatorArity {
+        path,
+        operator,
+        expected,
+        found,
+      } => write!(
+        f,
+        "operator \"{operator}\" at {path:?} expects {expected} arguments, \
+        found {found}"
+      ),
+    }
+  }
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
+  SyntaxGraph<C, E, O>
+{
+  fn resugar_children<S: DesugarStrategy>(
+    &self,
+    strategy: &S,
+    children: &[Sexp<(), S::InnerData>],
+    path_offset: usize,
+    context_tag: &C,
+    path: &mut Vec<usize>,
+    errors: &mut Vec<ResugarError>,
+  ) -> Vec<SyntaxTree<E, O>> {
+    children
+      .iter()
+      .enumerate()
+      .filter_map(|(i, child)| {
+        path.push(path_offset + i);
+        let child =
+          self.resugar_inner(strategy, child, context_tag, path, errors);
+        path.pop();
+        child
+      })
+      .collect()
+  }
+  fn resugar_inner<S: DesugarStrategy>(
+    &self,
+    strategy: &S,
+    sexp: &Sexp<(), S::InnerData>,
+    context_tag: &C,
+    path: &mut Vec<usize>,
+    errors: &mut Vec<ResugarError>,
+  ) -> Option<SyntaxTree<E, O>> {
+    let (inner_data, children) = match sexp {
+      Sexp::Leaf((), leaf) => {
+        return Some(Sexp::Leaf((), strategy.resugar_leaf(leaf)))
+      }
+      Sexp::Inner(inner_data, children) => (inner_data, children),
+    };
+    let context = self.get_context(context_tag);
+    let Some((tag, offset)) = strategy.tag(context, inner_data, children)
+    else {
+      errors.push(ResugarError::UnrecognizedForm { path: path.clone() });
+      self.resugar_children(strategy, children, 0, context_tag, path, errors);
+      return None;
+    };
+    if let Some(encloser) = context
+      .enclosers()
+      .iter()
+      .find(|encloser| encloser.id_str() == tag)
+    {
+      let children = self.resugar_children(
+        strategy,
+        &children[offset..],
+        offset,
+        self.get_encloser_context_tag(encloser),
+        path,
+        errors,
+      );
+      return Some(Sexp::Inner(
+        EncloserOrOperator::Encloser(encloser.clone()),
+        children,
+      ));
+    }
+    if let Some(operator) = context
+      .operators()
+      .iter()
+      .find(|operator| operator.id_str() == tag)
+    {
+      let expected = operator.left_args() + operator.right_args();
+      let found = children.len() - offset;
+      let arity_error =
+        (expected != found).then(|| ResugarError::OperatorArity {
+          path: path.clone(),
+          operator: tag.to_string(),
+          expected,
+          found,
+        });
+      errors.extend(arity_error);
+      let children = self.resugar_children(
+        strategy,
+        &children[offset..],
+        offset,
+        self.get_operator_context_tag(operator),
+        path,
+        errors,
+      );
+      return (expected == found).then(|| {
+        Sexp::Inner(EncloserOrOperator::Operator(operator.clone()), children)
+      });
+    }
+    errors.push(ResugarError::UnrecognizedForm { path: path.clone() });
+    self.resugar_children(strategy, children, 0, context_tag, path, errors);
+    None
+  }
+  pub fn resugar_with_in_context<S: DesugarStrategy>(
+    &self,
+    strategy: &S,
+    sexp: &Sexp<(), S::InnerData>,
+    context_tag: &C,
+  ) -> Result<SyntaxTree<E, O>, Vec<ResugarError>> {
+    let mut errors = vec![];
+    let tree =
+      self.resugar_inner(strategy, sexp, context_tag, &mut vec![], &mut errors);
+    match tree {
+      Some(tree) if errors.is_empty() => Ok(tree),
+      _ => Err(errors),
+    }
+  }
+  pub fn resugar_with<S: DesugarStrategy>(
+    &self,
+    strategy: &S,
+    sexp: &Sexp<(), S::InnerData>,
+  ) -> Result<SyntaxTree<E, O>, Vec<ResugarError>> {
+    self.resugar_with_in_context(strategy, sexp, &self.root)
+  }
+  pub fn resugar_in_context(
+    &self,
+    sexp: &RawSexp,
+    context_tag: &C,
+  ) -> Result<SyntaxTree<E, O>, Vec<ResugarError>> {
+    self.resugar_with_in_context(&PrefixTag, sexp, context_tag)
+  }
+  pub fn resugar(
+    &self,
+    sexp: &RawSexp,
+  ) -> Result<SyntaxTree<E, O>, Vec<ResugarError>> {
+    self.resugar_in_context(sexp, &self.root)
+  }
+}
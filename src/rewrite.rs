@@ -0,0 +1,252 @@
+use std::{
+  collections::HashSet,
+  fmt::{Debug, Display},
+  hash::Hash,
+  ops::Range,
+};
+
+use crate::{
+  document::{Document, TextEdit},
+  query::{Pattern, PatternError, PatternNode},
+  syntax::{ContextKind, EncloserOrOperator},
+  DocumentSyntaxTree, Encloser, Operator, ParseError, Parser, Sexp,
+  SyntaxGraph,
+};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RewriteError {
+  Pattern(PatternError),
+  Template(ParseError),
+  UnknownCapture(String),
+}
+
+impl Display for RewriteError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use RewriteError::*;
+    match self {
+      Pattern(pattern_error) => write!(f, "{pattern_error}"),
+      Template(parse_error) => {
+        write!(f, "couldn't parse template: {parse_error}")
+      }
+      UnknownCapture(name) => {
+        write!(f, "template refers to unknown capture \"{name}\"")
+      }
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RewriteMatch {
+  pub path: Vec<usize>,
+  pub range: Range<usize>,
+  pub original: String,
+  pub replacement: String,
+}
+
+#[derive(Clone, Debug)]
+enum Hole {
+  Capture(String),
+  Escape,
+}
+
+#[derive(Clone, Debug)]
+pub struct Rewrite<E: Encloser, O: Operator> {
+  pattern: Pattern<E, O>,
+  template: String,
+  holes: Vec<(Range<usize>, Hole)>,
+}
+
+fn capture_names<E: Encloser, O: Operator>(
+  pattern: &PatternNode<E, O>,
+  names: &mut HashSet<String>,
+) {
+  match pattern {
+    PatternNode::Literal(_) => {}
+    PatternNode::Wildcard { capture, .. } => names.extend(capture.clone()),
+    PatternNode::Inner(_, children) => {
+      for child in children {
+        capture_names(child, names);
+      }
+    }
+  }
+}
+
+fn hole_name(leaf: &str) -> Option<&str> {
+  let name = leaf.strip_prefix('?')?;
+  let name = name
+    .strip_suffix('*')
+    .or_else(|| name.strip_suffix('+'))
+    .unwrap_or(name);
+  Some(name.split_once(':').map(|(name, _)| name).unwrap_or(name))
+}
+
+fn collect_holes<
+  C: Clone + Debug + PartialEq + Eq + Hash,
+  E: Encloser,
+  O: Operator,
+>(
+  syntax_graph: &SyntaxGraph<C, E, O>,
+  context_tag: &C,
+  tree: &DocumentSyntaxTree<E, O>,
+  known_captures: &HashSet<String>,
+  holes: &mut Vec<(Range<usize>, Hole)>,
+) -> Result<(), RewriteError> {
+  match tree {
+    Sexp::Leaf(range, leaf) => {
+      if syntax_graph.get_context(context_tag).kind() != ContextKind::Code {
+        return Ok(());
+      }
+      if leaf.starts_with("??") {
+        holes.push((range.start..range.start + 1, Hole::Escape));
+      } else if let Some(name) = hole_name(leaf) {
+        if !known_captures.contains(name) {
+          return Err(RewriteError::UnknownCapture(name.to_string()));
+        }
+        holes.push((range.clone(), Hole::Capture(name.to_string())));
+      }
+    }
+    Sexp::Inner((_, encloser_or_operator), children) => {
+      let context_tag = match encloser_or_operator {
+        EncloserOrOperator::Encloser(encloser) => {
+          syntax_graph.get_encloser_context_tag(encloser)
+        }
+        EncloserOrOperator::Operator(operator) => {
+          syntax_graph.get_operator_context_tag(operator)
+        }
+      };
+      for child in children {
+        collect_holes(syntax_graph, context_tag, child, known_captures, holes)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+fn minimal_edit(
+  range: Range<usize>,
+  original: &str,
+  replacement: &str,
+) -> TextEdit {
+  let prefix_len = original
+    .char_indices()
+    .zip(replacement.chars())
+    .find(|((_, a), b)| a != b)
+    .map(|((i, _), _)| i)
+    .unwrap_or(original.len().min(replacement.len()));
+  let prefix_len = (0..=prefix_len)
+    .rev()
+    .find(|&i| original.is_char_boundary(i) && replacement.is_char_boundary(i))
+    .unwrap_or(0);
+  let original_rest = &original[prefix_len..];
+  let replacement_rest = &replacement[prefix_len..];
+  let suffix_len = original_rest
+    .chars()
+    .rev()
+    .zip(replacement_rest.chars().rev())
+    .take_while(|(a, b)| a == b)
+    .map(|(a, _)| a.len_utf8())
+    .sum::<usize>();
+  TextEdit::new(
+    range.start + prefix_len..range.end - suffix_len,
+    &replacement_rest[..replacement_rest.len() - suffix_len],
+  )
+}
+
+impl<E: Encloser, O: Operator> Rewrite<E, O> {
+  pub fn new<C: Clone + Debug + PartialEq + Eq + Hash>(
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    pattern: Pattern<E, O>,
+    template: &str,
+  ) -> Result<Self, RewriteError> {
+    let mut known_captures = HashSet::new();
+    capture_names(pattern.root(), &mut known_captures);
+    let mut holes = vec![];
+    for tree in Parser::new(syntax_graph.clone(), template).read_all() {
+      let tree = tree.map_err(RewriteError::Template)?;
+      collect_holes(
+        syntax_graph,
+        syntax_graph.root(),
+        &tree,
+        &known_captures,
+        &mut holes,
+      )?;
+    }
+    Ok(Self {
+      pattern,
+      template: template.to_string(),
+      holes,
+    })
+  }
+  pub fn parse<C: Clone + Debug + PartialEq + Eq + Hash>(
+    syntax_graph: &SyntaxGraph<C, E, O>,
+    pattern: &str,
+    template: &str,
+  ) -> Result<Self, RewriteError> {
+    let pattern =
+      Pattern::parse(syntax_graph, pattern).map_err(RewriteError::Pattern)?;
+    Self::new(syntax_graph, pattern, template)
+  }
+  pub fn pattern(&self) -> &Pattern<E, O> {
+    &self.pattern
+  }
+  pub fn template(&self) -> &str {
+    &self.template
+  }
+  pub fn matches<C: Clone + Debug + PartialEq + Eq + Hash>(
+    &self,
+    document: &Document<C, E, O>,
+  ) -> Vec<RewriteMatch> {
+    let mut rewrite_matches: Vec<RewriteMatch> = vec![];
+    for found in document.query(&self.pattern) {
+      let range = found.node.range().clone();
+      if rewrite_matches
+        .last()
+        .is_some_and(|previous| range.start < previous.range.end)
+      {
+        continue;
+      }
+      let mut replacement = String::new();
+      let mut copied_until = 0;
+      for (hole_range, hole) in &self.holes {
+        replacement.push_str(&self.template[copied_until..hole_range.start]);
+        if let Hole::Capture(name) = hole {
+          let captured = &found.captures[name];
+          if let (Some(first), Some(last)) = (captured.first(), captured.last())
+          {
+            replacement.push_str(
+              &document.text[first.node.range().start..last.node.range().end],
+            );
+          }
+        }
+        copied_until = hole_range.end;
+      }
+      replacement.push_str(&self.template[copied_until..]);
+      rewrite_matches.push(RewriteMatch {
+        path: found.path,
+        original: document.text[range.clone()].to_string(),
+        range,
+        replacement,
+      });
+    }
+    rewrite_matches
+  }
+  pub fn edits<C: Clone + Debug + PartialEq + Eq + Hash>(
+    &self,
+    document: &Document<C, E, O>,
+  ) -> Vec<TextEdit> {
+    self
+      .matches(document)
+      .into_iter()
+      .filter(|rewrite_match| {
+        rewrite_match.original != rewrite_match.replacement
+      })
+      .map(|rewrite_match| {
+        minimal_edit(
+          rewrite_match.range,
+          &rewrite_match.original,
+          &rewrite_match.replacement,
+        )
+      })
+      .collect()
+  }
+}
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use crate::{
   document::Document,
@@ -70,18 +70,16 @@ impl<'g> Operator for StringTaggedOperator<'g> {
   }
 }
 
+pub type ContextDescription<'g> =
+  (&'g str, Vec<&'g str>, Option<String>, Vec<String>);
+
 pub type StringTaggedSyntaxGraph<'g> =
   SyntaxGraph<&'g str, StringTaggedEncloser<'g>, StringTaggedOperator<'g>>;
 
 impl<'g> StringTaggedSyntaxGraph<'g> {
   pub fn from_descriptions(
     root: &'g str,
-    context_descriptions: Vec<(
-      &'g str,
-      Vec<&'g str>,
-      Option<String>,
-      Vec<String>,
-    )>,
+    context_descriptions: Vec<ContextDescription<'g>>,
     encloser_descriptions: Vec<(&'g str, &'g str, &'g str, &'g str)>,
     operator_descriptions: Vec<(&'g str, &'g str, usize, usize, &'g str)>,
   ) -> Self {
@@ -175,3 +173,204 @@ impl<'g> StringTaggedSyntaxGraph<'g> {
 
 pub type StringTaggedDocument<'t, 'g> =
   Document<'t, &'g str, StringTaggedEncloser<'g>, StringTaggedOperator<'g>>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedStringTaggedEncloser {
+  id: Arc<str>,
+  opener: Arc<str>,
+  closer: Arc<str>,
+}
+impl OwnedStringTaggedEncloser {
+  pub fn new(
+    id: impl Into<Arc<str>>,
+    opener: impl Into<Arc<str>>,
+    closer: impl Into<Arc<str>>,
+  ) -> Self {
+    Self {
+      id: id.into(),
+      opener: opener.into(),
+      closer: closer.into(),
+    }
+  }
+}
+impl Encloser for OwnedStringTaggedEncloser {
+  fn id_str(&self) -> &str {
+    &self.id
+  }
+
+  fn opening_encloser_str(&self) -> &str {
+    &self.opener
+  }
+
+  fn closing_encloser_str(&self) -> &str {
+    &self.closer
+  }
+}
+impl<'g> From<&StringTaggedEncloser<'g>> for OwnedStringTaggedEncloser {
+  fn from(encloser: &StringTaggedEncloser<'g>) -> Self {
+    Self::new(encloser.id, encloser.opener, encloser.closer)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct OwnedStringTaggedOperator {
+  id: Arc<str>,
+  operator: Arc<str>,
+  left_args: usize,
+  right_args: usize,
+}
+impl OwnedStringTaggedOperator {
+  pub fn new(
+    id: impl Into<Arc<str>>,
+    operator: impl Into<Arc<str>>,
+    left_args: usize,
+    right_args: usize,
+  ) -> Self {
+    Self {
+      id: id.into(),
+      operator: operator.into(),
+      left_args,
+      right_args,
+    }
+  }
+}
+impl Operator for OwnedStringTaggedOperator {
+  fn id_str(&self) -> &str {
+    &self.id
+  }
+  fn op_str(&self) -> &str {
+    &self.operator
+  }
+
+  fn left_args(&self) -> usize {
+    self.left_args
+  }
+
+  fn right_args(&self) -> usize {
+    self.right_args
+  }
+}
+impl<'g> From<&StringTaggedOperator<'g>> for OwnedStringTaggedOperator {
+  fn from(operator: &StringTaggedOperator<'g>) -> Self {
+    Self::new(
+      operator.id,
+      operator.operator,
+      operator.left_args,
+      operator.right_args,
+    )
+  }
+}
+
+pub type OwnedContextDescription<S> = (S, Vec<S>, Option<String>, Vec<String>);
+
+pub type OwnedStringTaggedSyntaxGraph =
+  SyntaxGraph<Arc<str>, OwnedStringTaggedEncloser, OwnedStringTaggedOperator>;
+
+impl OwnedStringTaggedSyntaxGraph {
+  pub fn from_descriptions<S: Into<Arc<str>>>(
+    root: S,
+    context_descriptions: Vec<OwnedContextDescription<S>>,
+    encloser_descriptions: Vec<(S, S, S, S)>,
+    operator_descriptions: Vec<(S, S, usize, usize, S)>,
+  ) -> Self {
+    let enclosers = encloser_descriptions
+      .into_iter()
+      .map(|(tag, opener, closer, context_tag)| {
+        (
+          OwnedStringTaggedEncloser::new(tag, opener, closer),
+          context_tag.into(),
+        )
+      })
+      .collect::<HashMap<_, Arc<str>>>();
+    let operators = operator_descriptions
+      .into_iter()
+      .map(|(tag, operator, left_args, right_args, context_tag)| {
+        (
+          OwnedStringTaggedOperator::new(tag, operator, left_args, right_args),
+          context_tag.into(),
+        )
+      })
+      .collect::<HashMap<_, Arc<str>>>();
+    Self::new(
+      root.into(),
+      context_descriptions
+        .into_iter()
+        .map(
+          |(context_name, internal_tags, escape_char, whitespace_chars)| {
+            let internal_tags = internal_tags
+              .into_iter()
+              .map(Into::into)
+              .collect::<Vec<Arc<str>>>();
+            let is_internal =
+              |id: &str| internal_tags.iter().any(|tag| &**tag == id);
+            (
+              context_name.into(),
+              SyntaxContext::new(
+                enclosers
+                  .keys()
+                  .filter(|encloser| is_internal(encloser.id_str()))
+                  .cloned()
+                  .collect(),
+                operators
+                  .keys()
+                  .filter(|operator| is_internal(operator.id_str()))
+                  .cloned()
+                  .collect(),
+                escape_char,
+                whitespace_chars,
+              ),
+            )
+          },
+        )
+        .collect(),
+      enclosers,
+      operators,
+    )
+  }
+}
+
+// Reader macros are typed over the borrowed encloser and operator types, so
+// they can't be carried across and are dropped by this conversion.
+impl<'g> From<&StringTaggedSyntaxGraph<'g>> for OwnedStringTaggedSyntaxGraph {
+  fn from(graph: &StringTaggedSyntaxGraph<'g>) -> Self {
+    Self::new(
+      graph.root().to_string().into(),
+      graph
+        .contexts()
+        .map(|(context_tag, context)| {
+          (
+            context_tag.to_string().into(),
+            SyntaxContext::new(
+              context.enclosers().iter().map(Into::into).collect(),
+              context.operators().iter().map(Into::into).collect(),
+              context.escape_char().map(str::to_string),
+              context.whitespace_chars().to_vec(),
+            )
+            .with_kind(context.kind()),
+          )
+        })
+        .collect(),
+      graph
+        .enclosers()
+        .map(|encloser| {
+          (
+            encloser.into(),
+            graph.get_encloser_context_tag(encloser).to_string().into(),
+          )
+        })
+        .collect(),
+      graph
+        .operators()
+        .map(|operator| {
+          (
+            operator.into(),
+            graph.get_operator_context_tag(operator).to_string().into(),
+          )
+        })
+        .collect(),
+    )
+  }
+}
+
+pub type OwnedStringTaggedDocument<'t> =
+  Document<'t, Arc<str>, OwnedStringTaggedEncloser, OwnedStringTaggedOperator>;
@@ -1,4 +1,10 @@
-use std::{collections::HashMap, fmt::Debug, hash::Hash};
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::{Debug, Display},
+  hash::Hash,
+};
+
+use crate::{builder::SyntaxGraphBuilder, reader_macro::ReaderMacro};
 
 pub trait Encloser: Debug + Clone + Eq + Hash {
   fn id_str(&self) -> &str;
@@ -13,12 +19,22 @@ pub trait Operator: Debug + Clone + Eq + Hash {
   fn op_str(&self) -> &str;
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ContextKind {
+  #[default]
+  Code,
+  Text,
+  Comment,
+}
+
 #[derive(Clone, Debug)]
 pub struct SyntaxContext<E: Encloser, O: Operator> {
+  kind: ContextKind,
   whitespace_chars: Vec<String>,
   pub(crate) escape_char: Option<String>,
   enclosers: Vec<E>,
   operators: Vec<O>,
+  reader_macros: Vec<ReaderMacro<E, O>>,
 }
 
 impl<'g, E: Encloser, O: Operator> SyntaxContext<E, O> {
@@ -29,18 +45,40 @@ impl<'g, E: Encloser, O: Operator> SyntaxContext<E, O> {
     whitespace_chars: Vec<String>,
   ) -> Self {
     Self {
+      kind: ContextKind::Code,
       whitespace_chars,
       escape_char,
       enclosers,
       operators,
+      reader_macros: vec![],
     }
   }
+  pub fn with_kind(mut self, kind: ContextKind) -> Self {
+    self.kind = kind;
+    self
+  }
+  pub fn with_reader_macro(mut self, reader_macro: ReaderMacro<E, O>) -> Self {
+    self.reader_macros.push(reader_macro);
+    self
+  }
+  pub fn kind(&self) -> ContextKind {
+    self.kind
+  }
   pub fn enclosers(&self) -> &[E] {
     &self.enclosers
   }
   pub fn operators(&self) -> &[O] {
     &self.operators
   }
+  pub fn reader_macros(&self) -> &[ReaderMacro<E, O>] {
+    &self.reader_macros
+  }
+  pub fn whitespace_chars(&self) -> &[String] {
+    &self.whitespace_chars
+  }
+  pub fn escape_char(&self) -> Option<&str> {
+    self.escape_char.as_deref()
+  }
   pub fn is_whitespace(&self, c: &str) -> bool {
     self.whitespace_chars.contains(&c.to_string())
   }
@@ -60,6 +98,70 @@ impl<E: Encloser, O: Operator> EncloserOrOperator<E, O> {
   }
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SyntaxGraphError<C: Clone + Debug + PartialEq + Eq + Hash> {
+  MissingRootContext(C),
+  DuplicateContext(C),
+  UndefinedContext { id: String, context: C },
+  ConflictingContexts { id: String, first: C, second: C },
+  MissingContext { id: String },
+  DuplicateId(String),
+  EmptyMarker { id: String },
+  DuplicateMarker { context: C, marker: String },
+  UnusedTag { id: String },
+  UnreachableContext(C),
+  UndefinedParentContext { context: C, parent: C },
+  ConflictingEscapeChars { context: C },
+  ConflictingContextKinds { context: C },
+}
+
+impl<C: Clone + Debug + PartialEq + Eq + Hash> Display for SyntaxGraphError<C> {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    use SyntaxGraphError::*;
+    match self {
+      MissingRootContext(context) => {
+        write!(f, "root context {context:?} is not defined")
+      }
+      DuplicateContext(context) => {
+        write!(f, "context {context:?} is defined more than once")
+      }
+      UndefinedContext { id, context } => {
+        write!(f, "\"{id}\" refers to undefined context {context:?}")
+      }
+      ConflictingContexts { id, first, second } => write!(
+        f,
+        "\"{id}\" is given both {first:?} and {second:?} as its context"
+      ),
+      MissingContext { id } => {
+        write!(
+          f,
+          "\"{id}\" is used in a context but has no context of its own"
+        )
+      }
+      DuplicateId(id) => write!(f, "id \"{id}\" is used more than once"),
+      EmptyMarker { id } => write!(f, "\"{id}\" has an empty marker"),
+      DuplicateMarker { context, marker } => write!(
+        f,
+        "marker \"{marker}\" is used more than once in context {context:?}"
+      ),
+      UnusedTag { id } => write!(f, "\"{id}\" isn't used in any context"),
+      UnreachableContext(context) => {
+        write!(f, "context {context:?} can't be reached from the root")
+      }
+      UndefinedParentContext { context, parent } => write!(
+        f,
+        "context {context:?} extends {parent:?}, which isn't defined before it"
+      ),
+      ConflictingEscapeChars { context } => {
+        write!(f, "context {context:?} is given conflicting escape chars")
+      }
+      ConflictingContextKinds { context } => {
+        write!(f, "context {context:?} is given conflicting kinds")
+      }
+    }
+  }
+}
+
 #[derive(Debug, Clone)]
 pub struct SyntaxGraph<
   C: Clone + Debug + PartialEq + Eq + Hash,
@@ -88,9 +190,32 @@ impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
       operator_contexts,
     }
   }
+  pub fn builder(root: C) -> SyntaxGraphBuilder<C, E, O> {
+    SyntaxGraphBuilder::new(root)
+  }
+  pub fn root(&self) -> &C {
+    &self.root
+  }
+  pub fn contexts(&self) -> impl Iterator<Item = (&C, &SyntaxContext<E, O>)> {
+    self.contexts.iter()
+  }
   pub fn get_context(&self, context_tag: &C) -> &SyntaxContext<E, O> {
     &self.contexts[&context_tag]
   }
+  pub fn add_reader_macro(
+    &mut self,
+    context_tag: &C,
+    reader_macro: ReaderMacro<E, O>,
+  ) -> Result<(), SyntaxGraphError<C>> {
+    let Some(context) = self.contexts.get_mut(context_tag) else {
+      return Err(SyntaxGraphError::UndefinedContext {
+        id: reader_macro.marker().to_string(),
+        context: context_tag.clone(),
+      });
+    };
+    context.reader_macros.push(reader_macro);
+    Ok(())
+  }
   pub fn get_encloser_context_tag(&self, encloser: &E) -> &C {
     &self.encloser_contexts[encloser]
   }
@@ -110,11 +235,134 @@ impl<C: Clone + Debug + PartialEq + Eq + Hash, E: Encloser, O: Operator>
       }
     }
   }
-  pub(crate) fn get_closers<'g>(&'g self, context_tag: &C) -> Vec<&'g str> {
-    self.contexts[context_tag]
-      .enclosers()
+  pub fn enclosers(&self) -> impl Iterator<Item = &E> {
+    self.encloser_contexts.keys()
+  }
+  pub fn operators(&self) -> impl Iterator<Item = &O> {
+    self.operator_contexts.keys()
+  }
+  pub fn validate(&self) -> Result<(), Vec<SyntaxGraphError<C>>> {
+    let mut errors = vec![];
+    if !self.contexts.contains_key(&self.root) {
+      errors.push(SyntaxGraphError::MissingRootContext(self.root.clone()));
+    }
+    let mut tagged = self
+      .encloser_contexts
       .iter()
-      .map(|encloser| encloser.closing_encloser_str())
-      .collect()
+      .map(|(encloser, context)| (encloser.id_str(), context))
+      .chain(
+        self
+          .operator_contexts
+          .iter()
+          .map(|(operator, context)| (operator.id_str(), context)),
+      )
+      .collect::<Vec<_>>();
+    tagged.sort_by_key(|(id, context)| (*id, format!("{context:?}")));
+    let mut ids = HashSet::new();
+    for (id, context) in &tagged {
+      if !ids.insert(*id) {
+        errors.push(SyntaxGraphError::DuplicateId(id.to_string()));
+      }
+      if !self.contexts.contains_key(context) {
+        errors.push(SyntaxGraphError::UndefinedContext {
+          id: id.to_string(),
+          context: (*context).clone(),
+        });
+      }
+    }
+    let mut enclosers = self.enclosers().collect::<Vec<_>>();
+    enclosers.sort_by_key(|encloser| encloser.id_str());
+    for encloser in enclosers {
+      if encloser.opening_encloser_str().is_empty()
+        || encloser.closing_encloser_str().is_empty()
+      {
+        errors.push(SyntaxGraphError::EmptyMarker {
+          id: encloser.id_str().to_string(),
+        });
+      }
+    }
+    let mut operators = self.operators().collect::<Vec<_>>();
+    operators.sort_by_key(|operator| operator.id_str());
+    for operator in operators {
+      if operator.op_str().is_empty() {
+        errors.push(SyntaxGraphError::EmptyMarker {
+          id: operator.id_str().to_string(),
+        });
+      }
+    }
+    let mut used = HashSet::new();
+    let contexts = self.sorted_contexts();
+    for &(context_tag, context) in &contexts {
+      let mut markers = HashSet::new();
+      let members = context
+        .enclosers
+        .iter()
+        .map(|encloser| {
+          (
+            encloser.id_str(),
+            encloser.opening_encloser_str(),
+            self.encloser_contexts.contains_key(encloser),
+          )
+        })
+        .chain(context.operators.iter().map(|operator| {
+          (
+            operator.id_str(),
+            operator.op_str(),
+            self.operator_contexts.contains_key(operator),
+          )
+        }))
+        .chain(context.reader_macros.iter().map(|reader_macro| {
+          (reader_macro.marker(), reader_macro.marker(), true)
+        }));
+      for (id, marker, has_context) in members {
+        used.insert(id);
+        if !has_context {
+          errors.push(SyntaxGraphError::MissingContext { id: id.to_string() });
+        }
+        if !marker.is_empty() && !markers.insert(marker) {
+          errors.push(SyntaxGraphError::DuplicateMarker {
+            context: context_tag.clone(),
+            marker: marker.to_string(),
+          });
+        }
+      }
+    }
+    for (id, _) in &tagged {
+      if !used.contains(id) {
+        errors.push(SyntaxGraphError::UnusedTag { id: id.to_string() });
+      }
+    }
+    let mut reachable = HashSet::from([&self.root]);
+    let mut frontier = vec![&self.root];
+    while let Some(context_tag) = frontier.pop() {
+      let Some(context) = self.contexts.get(context_tag) else {
+        continue;
+      };
+      let targets = context
+        .enclosers
+        .iter()
+        .filter_map(|encloser| self.encloser_contexts.get(encloser))
+        .chain(
+          context
+            .operators
+            .iter()
+            .filter_map(|operator| self.operator_contexts.get(operator)),
+        );
+      for target in targets {
+        if reachable.insert(target) {
+          frontier.push(target);
+        }
+      }
+    }
+    for (context_tag, _) in contexts {
+      if !reachable.contains(context_tag) {
+        errors.push(SyntaxGraphError::UnreachableContext(context_tag.clone()));
+      }
+    }
+    if errors.is_empty() {
+      Ok(())
+    } else {
+      Err(errors)
+    }
   }
 }
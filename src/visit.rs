@@ -0,0 +1,251 @@
+use std::fmt::Debug;
+
+use crate::Sexp;
+
+pub trait Visitor<
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+>
+{
+  fn enter(
+    &mut self,
+    _path: &[usize],
+    _node: &Sexp<LeafData, InnerData>,
+  ) -> bool {
+    true
+  }
+  fn exit(&mut self, _path: &[usize], _node: &Sexp<LeafData, InnerData>) {}
+}
+
+pub trait VisitorMut<
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+>
+{
+  fn enter(
+    &mut self,
+    _path: &[usize],
+    _node: &mut Sexp<LeafData, InnerData>,
+  ) -> bool {
+    true
+  }
+  fn exit_inner(&mut self, _path: &[usize], _data: &mut InnerData) {}
+}
+
+pub struct PreOrder<
+  's,
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+> {
+  stack: Vec<(Vec<usize>, &'s Sexp<LeafData, InnerData>)>,
+}
+
+impl<
+    's,
+    LeafData: Clone + PartialEq + Eq + Debug,
+    InnerData: Clone + PartialEq + Eq + Debug,
+  > Iterator for PreOrder<'s, LeafData, InnerData>
+{
+  type Item = (Vec<usize>, &'s Sexp<LeafData, InnerData>);
+  fn next(&mut self) -> Option<Self::Item> {
+    let (path, node) = self.stack.pop()?;
+    if let Sexp::Inner(_, children) = node {
+      self
+        .stack
+        .extend(children.iter().enumerate().rev().map(|(i, child)| {
+          let mut child_path = path.clone();
+          child_path.push(i);
+          (child_path, child)
+        }));
+    }
+    Some((path, node))
+  }
+}
+
+pub struct PostOrder<
+  's,
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+> {
+  stack: Vec<(Vec<usize>, &'s Sexp<LeafData, InnerData>, bool)>,
+}
+
+impl<
+    's,
+    LeafData: Clone + PartialEq + Eq + Debug,
+    InnerData: Clone + PartialEq + Eq + Debug,
+  > Iterator for PostOrder<'s, LeafData, InnerData>
+{
+  type Item = (Vec<usize>, &'s Sexp<LeafData, InnerData>);
+  fn next(&mut self) -> Option<Self::Item> {
+    loop {
+      let (path, node, expanded) = self.stack.pop()?;
+      match node {
+        Sexp::Inner(_, children) if !expanded => {
+          self.stack.push((path.clone(), node, true));
+          self.stack.extend(children.iter().enumerate().rev().map(
+            |(i, child)| {
+              let mut child_path = path.clone();
+              child_path.push(i);
+              (child_path, child, false)
+            },
+          ));
+        }
+        _ => return Some((path, node)),
+      }
+    }
+  }
+}
+
+impl<
+    LeafData: Clone + PartialEq + Eq + Debug,
+    InnerData: Clone + PartialEq + Eq + Debug,
+  > Sexp<LeafData, InnerData>
+{
+  pub fn pre_order(&self) -> PreOrder<'_, LeafData, InnerData> {
+    PreOrder {
+      stack: vec![(vec![], self)],
+    }
+  }
+  pub fn post_order(&self) -> PostOrder<'_, LeafData, InnerData> {
+    PostOrder {
+      stack: vec![(vec![], self, false)],
+    }
+  }
+  pub fn visit(&self, visitor: &mut impl Visitor<LeafData, InnerData>) {
+    let mut stack = vec![(vec![], self, false)];
+    while let Some((path, node, entered)) = stack.pop() {
+      if entered {
+        visitor.exit(&path, node);
+        continue;
+      }
+      if !visitor.enter(&path, node) {
+        continue;
+      }
+      stack.push((path.clone(), node, true));
+      if let Sexp::Inner(_, children) = node {
+        stack.extend(children.iter().enumerate().rev().map(|(i, child)| {
+          let mut child_path = path.clone();
+          child_path.push(i);
+          (child_path, child, false)
+        }));
+      }
+    }
+  }
+  pub fn visit_mut(
+    &mut self,
+    visitor: &mut impl VisitorMut<LeafData, InnerData>,
+  ) {
+    let mut stack = vec![];
+    let mut path = vec![];
+    if !visitor.enter(&path, self) {
+      return;
+    }
+    if let Sexp::Inner(data, children) = self {
+      stack.push((data, children.iter_mut().enumerate()));
+    }
+    while let Some((_, children)) = stack.last_mut() {
+      match children.next() {
+        Some((i, child)) => {
+          path.push(i);
+          if visitor.enter(&path, child) {
+            if let Sexp::Inner(data, children) = child {
+              stack.push((data, children.iter_mut().enumerate()));
+              continue;
+            }
+          }
+          path.pop();
+        }
+        None => {
+          let (data, _) = stack.pop().unwrap();
+          visitor.exit_inner(&path, data);
+          path.pop();
+        }
+      }
+    }
+  }
+  pub fn fold<A>(
+    &self,
+    mut leaf_fn: impl FnMut(&LeafData, &str) -> A,
+    mut inner_fn: impl FnMut(&InnerData, Vec<A>) -> A,
+  ) -> A {
+    let mut stack = vec![];
+    let mut current = self;
+    loop {
+      let mut value = match current {
+        Sexp::Leaf(data, leaf) => leaf_fn(data, leaf),
+        Sexp::Inner(data, children) => match children.split_first() {
+          Some((first, rest)) => {
+            stack.push((data, rest.iter(), vec![]));
+            current = first;
+            continue;
+          }
+          None => inner_fn(data, vec![]),
+        },
+      };
+      loop {
+        let Some((_, remaining_children, values)) = stack.last_mut() else {
+          return value;
+        };
+        values.push(value);
+        if let Some(next) = remaining_children.next() {
+          current = next;
+          break;
+        }
+        let (data, _, values) = stack.pop().unwrap();
+        value = inner_fn(data, values);
+      }
+    }
+  }
+  pub fn into_fold<A>(
+    self,
+    mut leaf_fn: impl FnMut(LeafData, String) -> A,
+    mut inner_fn: impl FnMut(InnerData, Vec<A>) -> A,
+  ) -> A {
+    let mut stack = vec![];
+    let mut current = self;
+    loop {
+      let mut value = match current {
+        Sexp::Leaf(data, leaf) => leaf_fn(data, leaf),
+        Sexp::Inner(data, children) => {
+          let mut children = children.into_iter();
+          match children.next() {
+            Some(first) => {
+              stack.push((data, children, vec![]));
+              current = first;
+              continue;
+            }
+            None => inner_fn(data, vec![]),
+          }
+        }
+      };
+      loop {
+        let Some((_, remaining_children, values)) = stack.last_mut() else {
+          return value;
+        };
+        values.push(value);
+        if let Some(next) = remaining_children.next() {
+          current = next;
+          break;
+        }
+        let (data, _, values) = stack.pop().unwrap();
+        value = inner_fn(data, values);
+      }
+    }
+  }
+  pub fn map_leaf_data<NewLeafData: Clone + PartialEq + Eq + Debug>(
+    self,
+    mut f: impl FnMut(LeafData) -> NewLeafData,
+  ) -> Sexp<NewLeafData, InnerData> {
+    self.into_fold(|data, leaf| Sexp::Leaf(f(data), leaf), Sexp::Inner)
+  }
+  pub fn map_inner_data<NewInnerData: Clone + PartialEq + Eq + Debug>(
+    self,
+    mut f: impl FnMut(InnerData) -> NewInnerData,
+  ) -> Sexp<LeafData, NewInnerData> {
+    self.into_fold(Sexp::Leaf, |data, children| Sexp::Inner(f(data), children))
+  }
+  pub fn map_leaves(self, mut f: impl FnMut(String) -> String) -> Self {
+    self.into_fold(|data, leaf| Sexp::Leaf(data, f(leaf)), Sexp::Inner)
+  }
+}
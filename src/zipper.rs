@@ -0,0 +1,180 @@
+use std::fmt::Debug;
+
+use crate::{ast::InvalidTreePath, Sexp};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ZipperFrame<
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+> {
+  data: InnerData,
+  left: Vec<Sexp<LeafData, InnerData>>,
+  reversed_right: Vec<Sexp<LeafData, InnerData>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Zipper<
+  LeafData: Clone + PartialEq + Eq + Debug,
+  InnerData: Clone + PartialEq + Eq + Debug,
+> {
+  focus: Sexp<LeafData, InnerData>,
+  frames: Vec<ZipperFrame<LeafData, InnerData>>,
+}
+
+impl<
+    LeafData: Clone + PartialEq + Eq + Debug,
+    InnerData: Clone + PartialEq + Eq + Debug,
+  > Zipper<LeafData, InnerData>
+{
+  pub fn new(root: Sexp<LeafData, InnerData>) -> Self {
+    Self {
+      focus: root,
+      frames: vec![],
+    }
+  }
+  pub fn from_path(
+    root: Sexp<LeafData, InnerData>,
+    path: &[usize],
+  ) -> Result<Self, InvalidTreePath> {
+    root.get_subtree(path)?;
+    let mut zipper = Self::new(root);
+    for &child_index in path {
+      zipper.down_to(child_index);
+    }
+    Ok(zipper)
+  }
+  pub fn focus(&self) -> &Sexp<LeafData, InnerData> {
+    &self.focus
+  }
+  pub fn focus_mut(&mut self) -> &mut Sexp<LeafData, InnerData> {
+    &mut self.focus
+  }
+  pub fn path(&self) -> Vec<usize> {
+    self.frames.iter().map(|frame| frame.left.len()).collect()
+  }
+  pub fn is_root(&self) -> bool {
+    self.frames.is_empty()
+  }
+  pub fn down_to(&mut self, child_index: usize) -> Option<&mut Self> {
+    let Sexp::Inner(_, children) = &mut self.focus else {
+      return None;
+    };
+    if child_index >= children.len() {
+      return None;
+    }
+    let mut reversed_right = children.split_off(child_index + 1);
+    reversed_right.reverse();
+    let focus = children.pop().unwrap();
+    let Sexp::Inner(data, left) = std::mem::replace(&mut self.focus, focus)
+    else {
+      unreachable!()
+    };
+    self.frames.push(ZipperFrame {
+      data,
+      left,
+      reversed_right,
+    });
+    Some(self)
+  }
+  pub fn down(&mut self) -> Option<&mut Self> {
+    self.down_to(0)
+  }
+  pub fn up(&mut self) -> Option<&mut Self> {
+    let ZipperFrame {
+      data,
+      mut left,
+      mut reversed_right,
+    } = self.frames.pop()?;
+    reversed_right.reverse();
+    let focus = std::mem::replace(&mut self.focus, Sexp::Inner(data, vec![]));
+    left.push(focus);
+    left.append(&mut reversed_right);
+    if let Sexp::Inner(_, children) = &mut self.focus {
+      *children = left;
+    }
+    Some(self)
+  }
+  pub fn left(&mut self) -> Option<&mut Self> {
+    let frame = self.frames.last_mut()?;
+    let sibling = frame.left.pop()?;
+    let focus = std::mem::replace(&mut self.focus, sibling);
+    frame.reversed_right.push(focus);
+    Some(self)
+  }
+  pub fn right(&mut self) -> Option<&mut Self> {
+    let frame = self.frames.last_mut()?;
+    let sibling = frame.reversed_right.pop()?;
+    let focus = std::mem::replace(&mut self.focus, sibling);
+    frame.left.push(focus);
+    Some(self)
+  }
+  pub fn root(&mut self) -> &mut Self {
+    while self.up().is_some() {}
+    self
+  }
+  pub fn into_root(mut self) -> Sexp<LeafData, InnerData> {
+    self.root();
+    self.focus
+  }
+  pub fn replace(
+    &mut self,
+    sexp: Sexp<LeafData, InnerData>,
+  ) -> Sexp<LeafData, InnerData> {
+    std::mem::replace(&mut self.focus, sexp)
+  }
+  pub fn insert_left(
+    &mut self,
+    sexp: Sexp<LeafData, InnerData>,
+  ) -> Option<&mut Self> {
+    self.frames.last_mut()?.left.push(sexp);
+    Some(self)
+  }
+  pub fn insert_right(
+    &mut self,
+    sexp: Sexp<LeafData, InnerData>,
+  ) -> Option<&mut Self> {
+    self.frames.last_mut()?.reversed_right.push(sexp);
+    Some(self)
+  }
+  pub fn insert_child(
+    &mut self,
+    child_index: usize,
+    sexp: Sexp<LeafData, InnerData>,
+  ) -> Option<&mut Self> {
+    let Sexp::Inner(_, children) = &mut self.focus else {
+      return None;
+    };
+    if child_index > children.len() {
+      return None;
+    }
+    children.insert(child_index, sexp);
+    Some(self)
+  }
+  pub fn remove(&mut self) -> Option<Sexp<LeafData, InnerData>> {
+    let frame = self.frames.last_mut()?;
+    let replacement = match frame.reversed_right.pop() {
+      Some(right_sibling) => right_sibling,
+      None => match frame.left.pop() {
+        Some(left_sibling) => left_sibling,
+        None => {
+          let frame = self.frames.pop().unwrap();
+          return Some(std::mem::replace(
+            &mut self.focus,
+            Sexp::Inner(frame.data, vec![]),
+          ));
+        }
+      },
+    };
+    Some(std::mem::replace(&mut self.focus, replacement))
+  }
+}
+
+impl<
+    LeafData: Clone + PartialEq + Eq + Debug,
+    InnerData: Clone + PartialEq + Eq + Debug,
+  > Sexp<LeafData, InnerData>
+{
+  pub fn zipper(self) -> Zipper<LeafData, InnerData> {
+    Zipper::new(self)
+  }
+}
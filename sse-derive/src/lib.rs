@@ -0,0 +1,428 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+  bracketed, meta::ParseNestedMeta, parse_macro_input, punctuated::Punctuated,
+  Attribute, Data, DeriveInput, Error, Ident, LitInt, LitStr, Path, Result,
+  Token, Type, Variant,
+};
+
+fn unit_variants(input: &DeriveInput) -> Result<Vec<&Variant>> {
+  let Data::Enum(data) = &input.data else {
+    return Err(Error::new_spanned(input, "expected an enum"));
+  };
+  data
+    .variants
+    .iter()
+    .map(|variant| {
+      if variant.fields.is_empty() {
+        Ok(variant)
+      } else {
+        Err(Error::new_spanned(variant, "expected a unit variant"))
+      }
+    })
+    .collect()
+}
+
+fn parse_attrs(
+  attrs: &[Attribute],
+  name: &str,
+  mut parse: impl FnMut(ParseNestedMeta) -> Result<()>,
+) -> Result<()> {
+  for attr in attrs.iter().filter(|attr| attr.path().is_ident(name)) {
+    attr.parse_nested_meta(&mut parse)?;
+  }
+  Ok(())
+}
+
+fn crate_path(meta: &ParseNestedMeta) -> Result<Path> {
+  meta.value()?.parse::<LitStr>()?.parse()
+}
+
+fn default_crate_path() -> Path {
+  syn::parse_quote!(::sse)
+}
+
+fn bracketed_list<T: syn::parse::Parse>(
+  meta: &ParseNestedMeta,
+) -> Result<Vec<T>> {
+  let value = meta.value()?;
+  let content;
+  bracketed!(content in value);
+  Ok(
+    Punctuated::<T, Token![,]>::parse_terminated(&content)?
+      .into_iter()
+      .collect(),
+  )
+}
+
+#[derive(Default)]
+struct MemberAttrs {
+  id: Option<LitStr>,
+  markers: Vec<(&'static str, LitStr)>,
+  counts: Vec<(&'static str, LitInt)>,
+  context: Option<Ident>,
+  within: Option<Vec<Ident>>,
+}
+
+impl MemberAttrs {
+  fn parse(
+    variant: &Variant,
+    attr_name: &str,
+    marker_names: &[&'static str],
+    count_names: &[&'static str],
+  ) -> Result<Self> {
+    let mut attrs = Self::default();
+    parse_attrs(&variant.attrs, attr_name, |meta| {
+      if meta.path.is_ident("id") {
+        attrs.id = Some(meta.value()?.parse()?);
+      } else if meta.path.is_ident("context") {
+        attrs.context = Some(meta.value()?.parse()?);
+      } else if meta.path.is_ident("within") {
+        attrs.within = Some(bracketed_list(&meta)?);
+      } else if let Some(name) =
+        marker_names.iter().find(|name| meta.path.is_ident(name))
+      {
+        attrs.markers.push((name, meta.value()?.parse()?));
+      } else if let Some(name) =
+        count_names.iter().find(|name| meta.path.is_ident(name))
+      {
+        attrs.counts.push((name, meta.value()?.parse()?));
+      } else {
+        return Err(meta.error(format!("unknown {attr_name} attribute")));
+      }
+      Ok(())
+    })?;
+    for name in marker_names {
+      if !attrs
+        .markers
+        .iter()
+        .any(|(marker_name, _)| marker_name == name)
+      {
+        return Err(Error::new_spanned(
+          variant,
+          format!("missing `#[{attr_name}({name} = \"...\")]`"),
+        ));
+      }
+    }
+    Ok(attrs)
+  }
+  fn id(&self, variant: &Variant) -> LitStr {
+    self.id.clone().unwrap_or_else(|| {
+      LitStr::new(&variant.ident.to_string(), variant.ident.span())
+    })
+  }
+  fn marker(&self, name: &str) -> &LitStr {
+    &self
+      .markers
+      .iter()
+      .find(|(marker_name, _)| *marker_name == name)
+      .unwrap()
+      .1
+  }
+  fn count(&self, name: &str) -> TokenStream2 {
+    match self
+      .counts
+      .iter()
+      .find(|(count_name, _)| *count_name == name)
+    {
+      Some((_, count)) => quote!(#count),
+      None => quote!(0),
+    }
+  }
+}
+
+struct TypeAttrs {
+  crate_path: Path,
+  context_type: Option<Type>,
+  within: Option<Vec<Ident>>,
+}
+
+impl TypeAttrs {
+  fn parse(input: &DeriveInput, attr_name: &str) -> Result<Self> {
+    let mut attrs = Self {
+      crate_path: default_crate_path(),
+      context_type: None,
+      within: None,
+    };
+    parse_attrs(&input.attrs, attr_name, |meta| {
+      if meta.path.is_ident("crate") {
+        attrs.crate_path = crate_path(&meta)?;
+      } else if meta.path.is_ident("context") {
+        attrs.context_type = Some(meta.value()?.parse()?);
+      } else if meta.path.is_ident("within") {
+        attrs.within = Some(bracketed_list(&meta)?);
+      } else {
+        return Err(meta.error(format!("unknown {attr_name} attribute")));
+      }
+      Ok(())
+    })?;
+    Ok(attrs)
+  }
+}
+
+fn graph_members_impl(
+  input: &DeriveInput,
+  type_attrs: &TypeAttrs,
+  members: &[(&Variant, MemberAttrs)],
+) -> Result<TokenStream2> {
+  let Some(context_type) = &type_attrs.context_type else {
+    return Ok(quote!());
+  };
+  let crate_path = &type_attrs.crate_path;
+  let name = &input.ident;
+  let members = members
+    .iter()
+    .map(|(variant, attrs)| {
+      let variant_name = &variant.ident;
+      let context = attrs.context.as_ref().ok_or_else(|| {
+        Error::new_spanned(variant, "missing `context = ...` attribute")
+      })?;
+      let within = attrs
+        .within
+        .as_ref()
+        .or(type_attrs.within.as_ref())
+        .ok_or_else(|| {
+          Error::new_spanned(
+            variant,
+            "missing `within = [...]` on the variant or the type",
+          )
+        })?;
+      Ok(quote! {
+        #crate_path::builder::GraphMember {
+          member: #name::#variant_name,
+          target: #context_type::#context,
+          within: vec![#(#context_type::#within),*],
+        }
+      })
+    })
+    .collect::<Result<Vec<_>>>()?;
+  Ok(quote! {
+    impl #crate_path::builder::GraphMembers<#context_type> for #name {
+      fn graph_members(
+      ) -> Vec<#crate_path::builder::GraphMember<Self, #context_type>> {
+        vec![#(#members),*]
+      }
+    }
+  })
+}
+
+fn match_arms(
+  input: &DeriveInput,
+  members: &[(&Variant, MemberAttrs)],
+  value: impl Fn(&Variant, &MemberAttrs) -> TokenStream2,
+) -> TokenStream2 {
+  let name = &input.ident;
+  let arms = members.iter().map(|(variant, attrs)| {
+    let variant_name = &variant.ident;
+    let value = value(variant, attrs);
+    quote!(#name::#variant_name => #value)
+  });
+  quote! {
+    match self {
+      #(#arms,)*
+    }
+  }
+}
+
+fn derive_encloser_impl(input: DeriveInput) -> Result<TokenStream2> {
+  let members = unit_variants(&input)?
+    .into_iter()
+    .map(|variant| {
+      Ok((
+        variant,
+        MemberAttrs::parse(variant, "encloser", &["open", "close"], &[])?,
+      ))
+    })
+    .collect::<Result<Vec<_>>>()?;
+  let name = &input.ident;
+  let id = match_arms(&input, &members, |variant, attrs| {
+    let id = attrs.id(variant);
+    quote!(#id)
+  });
+  let open = match_arms(&input, &members, |_, attrs| {
+    let open = attrs.marker("open");
+    quote!(#open)
+  });
+  let close = match_arms(&input, &members, |_, attrs| {
+    let close = attrs.marker("close");
+    quote!(#close)
+  });
+  let type_attrs = TypeAttrs::parse(&input, "encloser")?;
+  let crate_path = &type_attrs.crate_path;
+  let graph_members = graph_members_impl(&input, &type_attrs, &members)?;
+  Ok(quote! {
+    impl #crate_path::Encloser for #name {
+      fn id_str(&self) -> &str {
+        #id
+      }
+      fn opening_encloser_str(&self) -> &str {
+        #open
+      }
+      fn closing_encloser_str(&self) -> &str {
+        #close
+      }
+    }
+    #graph_members
+  })
+}
+
+fn derive_operator_impl(input: DeriveInput) -> Result<TokenStream2> {
+  let members = unit_variants(&input)?
+    .into_iter()
+    .map(|variant| {
+      Ok((
+        variant,
+        MemberAttrs::parse(variant, "operator", &["op"], &["left", "right"])?,
+      ))
+    })
+    .collect::<Result<Vec<_>>>()?;
+  let name = &input.ident;
+  let id = match_arms(&input, &members, |variant, attrs| {
+    let id = attrs.id(variant);
+    quote!(#id)
+  });
+  let op = match_arms(&input, &members, |_, attrs| {
+    let op = attrs.marker("op");
+    quote!(#op)
+  });
+  let left = match_arms(&input, &members, |_, attrs| attrs.count("left"));
+  let right = match_arms(&input, &members, |_, attrs| attrs.count("right"));
+  let type_attrs = TypeAttrs::parse(&input, "operator")?;
+  let crate_path = &type_attrs.crate_path;
+  let graph_members = graph_members_impl(&input, &type_attrs, &members)?;
+  Ok(quote! {
+    impl #crate_path::Operator for #name {
+      fn id_str(&self) -> &str {
+        #id
+      }
+      fn left_args(&self) -> usize {
+        #left
+      }
+      fn right_args(&self) -> usize {
+        #right
+      }
+      fn op_str(&self) -> &str {
+        #op
+      }
+    }
+    #graph_members
+  })
+}
+
+fn derive_syntax_graph_impl(input: DeriveInput) -> Result<TokenStream2> {
+  let name = &input.ident;
+  let (mut encloser_type, mut operator_type) = (None::<Type>, None::<Type>);
+  let mut crate_path = default_crate_path();
+  parse_attrs(&input.attrs, "syntax_graph", |meta| {
+    if meta.path.is_ident("crate") {
+      crate_path = self::crate_path(&meta)?;
+    } else if meta.path.is_ident("encloser") {
+      encloser_type = Some(meta.value()?.parse()?);
+    } else if meta.path.is_ident("operator") {
+      operator_type = Some(meta.value()?.parse()?);
+    } else {
+      return Err(meta.error("unknown syntax_graph attribute"));
+    }
+    Ok(())
+  })?;
+  let (Some(encloser_type), Some(operator_type)) =
+    (encloser_type, operator_type)
+  else {
+    return Err(Error::new_spanned(
+      &input.ident,
+      "missing `#[syntax_graph(encloser = ..., operator = ...)]`",
+    ));
+  };
+  let mut root = None;
+  let mut contexts = vec![];
+  for variant in unit_variants(&input)? {
+    let variant_name = &variant.ident;
+    let mut is_root = false;
+    let mut parent = None::<Ident>;
+    let mut settings = vec![];
+    parse_attrs(&variant.attrs, "context", |meta| {
+      if meta.path.is_ident("root") {
+        is_root = true;
+      } else if meta.path.is_ident("extends") {
+        parent = Some(meta.value()?.parse()?);
+      } else if meta.path.is_ident("whitespace") {
+        let whitespace_chars = bracketed_list::<LitStr>(&meta)?;
+        settings.push(quote!(.with_whitespace_chars([#(#whitespace_chars),*])));
+      } else if meta.path.is_ident("escape") {
+        let escape_char: LitStr = meta.value()?.parse()?;
+        settings.push(quote!(.with_escape_char(#escape_char)));
+      } else if meta.path.is_ident("kind") {
+        let kind: Ident = meta.value()?.parse()?;
+        settings.push(quote!(.with_kind(#crate_path::ContextKind::#kind)));
+      } else {
+        return Err(meta.error("unknown context attribute"));
+      }
+      Ok(())
+    })?;
+    if is_root {
+      if root.is_some() {
+        return Err(Error::new_spanned(variant, "more than one root context"));
+      }
+      root = Some(variant_name);
+    }
+    let build_context = quote! {
+      |context| {
+        context
+          #(#settings)*
+          .with_graph_members(&#name::#variant_name)
+      }
+    };
+    contexts.push(match parent {
+      Some(parent) => quote! {
+        .with_context_extending(
+          #name::#variant_name,
+          #name::#parent,
+          #build_context,
+        )
+      },
+      None => quote! {
+        .with_context(#name::#variant_name, #build_context)
+      },
+    });
+  }
+  let Some(root) = root else {
+    return Err(Error::new_spanned(
+      &input.ident,
+      "missing `#[context(root)]` on one of the variants",
+    ));
+  };
+  Ok(quote! {
+    impl #name {
+      pub fn syntax_graph() -> Result<
+        #crate_path::SyntaxGraph<#name, #encloser_type, #operator_type>,
+        Vec<#crate_path::SyntaxGraphError<#name>>,
+      > {
+        #crate_path::SyntaxGraph::builder(#name::#root)
+          #(#contexts)*
+          .build()
+      }
+    }
+  })
+}
+
+#[proc_macro_derive(Encloser, attributes(encloser))]
+pub fn derive_encloser(input: TokenStream) -> TokenStream {
+  derive_encloser_impl(parse_macro_input!(input as DeriveInput))
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+#[proc_macro_derive(Operator, attributes(operator))]
+pub fn derive_operator(input: TokenStream) -> TokenStream {
+  derive_operator_impl(parse_macro_input!(input as DeriveInput))
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+#[proc_macro_derive(SyntaxGraph, attributes(syntax_graph, context))]
+pub fn derive_syntax_graph(input: TokenStream) -> TokenStream {
+  derive_syntax_graph_impl(parse_macro_input!(input as DeriveInput))
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}